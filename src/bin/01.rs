@@ -1,3 +1,4 @@
+use advent_of_code::parse::records;
 use fxhash::{FxBuildHasher, FxHashMap};
 
 advent_of_code::solution!(1);
@@ -10,26 +11,10 @@ fn get_abs(n: i32) -> i32 {
 fn parse_file_content1(file_content: &str) -> (Vec<i32>, Vec<i32>) {
     let mut left: Vec<i32> = Vec::with_capacity(1000);
     let mut right: Vec<i32> = Vec::with_capacity(1000);
-    let lists = [&mut left, &mut right];
-    let mut value: i32 = 0;
-    let mut in_value = false;
-    let mut was_in_value: bool;
-    let mut insert_index: usize = 0;
 
-    for c in file_content.bytes() {
-        was_in_value = in_value;
-        in_value = c.is_ascii_digit();
-        if in_value {
-            value = value * 10 + (c - b'0') as i32;
-        } else if was_in_value {
-            lists[insert_index].push(value);
-            insert_index = 1 - insert_index;
-            value = 0;
-        }
-    }
-
-    if in_value {
-        right.push(value);
+    for [left_value, right_value] in records(file_content) {
+        left.push(left_value);
+        right.push(right_value);
     }
 
     (left, right)
@@ -39,30 +24,10 @@ fn parse_file_content2(file_content: &str) -> (Vec<i32>, FxHashMap<i32, i32>) {
     let mut left: Vec<i32> = Vec::with_capacity(1000);
     let mut right_keys: FxHashMap<i32, i32> =
         FxHashMap::with_capacity_and_hasher(1000, FxBuildHasher::default());
-    let mut left_inserter = |v| left.push(v);
-    let mut right_inserter = |v| {
-        right_keys.entry(v).and_modify(|val| *val += 1).or_insert(1);
-    };
-    let mut inserters: Vec<&mut dyn FnMut(i32)> = vec![&mut left_inserter, &mut right_inserter];
-    let mut value: i32 = 0;
-    let mut in_value = false;
-    let mut was_in_value: bool;
-    let mut insert_index: usize = 0;
-
-    for c in file_content.bytes() {
-        was_in_value = in_value;
-        in_value = c.is_ascii_digit();
-        if in_value {
-            value = value * 10 + (c - b'0') as i32;
-        } else if was_in_value {
-            inserters[insert_index](value);
-            insert_index = 1 - insert_index;
-            value = 0;
-        }
-    }
 
-    if in_value {
-        inserters[insert_index](value);
+    for [left_value, right_value] in records(file_content) {
+        left.push(left_value);
+        *right_keys.entry(right_value).or_insert(0) += 1;
     }
 
     (left, right_keys)
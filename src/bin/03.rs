@@ -1,8 +1,9 @@
+use advent_of_code::parse::combinators::unsigned;
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::{anychar, digit1},
-    combinator::{map, map_res, value},
+    character::complete::anychar,
+    combinator::{map, value},
     multi::many_till,
     IResult,
 };
@@ -25,15 +26,11 @@ impl Instruction {
     }
 }
 
-fn number(input: &str) -> IResult<&str, u32> {
-    map_res(digit1, str::parse)(input)
-}
-
 fn find_mul(i: &str) -> IResult<&str, Instruction> {
     let (i, _) = tag("mul(")(i)?;
-    let (i, lhs) = number(i)?;
+    let (i, lhs) = unsigned(i)?;
     let (i, _) = tag(",")(i)?;
-    let (i, rhs) = number(i)?;
+    let (i, rhs) = unsigned(i)?;
     let (i, _) = tag(")")(i)?;
 
     Ok((i, Instruction::Multiply(lhs, rhs)))
@@ -44,8 +44,8 @@ fn count_latnoziroh(grid: &[Vec<u8>]) -> u32 {
 fn count_vertical(grid: &[Vec<u8>]) -> u32 {
     let mut count = 0;
     for y in 0..grid.len() - 3 {
-        for x in 0..grid[y].len() {
-            if grid[y][x] == b'X'
+        for (x, &cell) in grid[y].iter().enumerate() {
+            if cell == b'X'
                 && grid[y + 1][x] == b'M'
                 && grid[y + 2][x] == b'A'
                 && grid[y + 3][x] == b'S'
@@ -60,8 +60,8 @@ fn count_vertical(grid: &[Vec<u8>]) -> u32 {
 fn count_lacitrev(grid: &[Vec<u8>]) -> u32 {
     let mut count = 0;
     for y in 3..grid.len() {
-        for x in 0..grid[y].len() {
-            if grid[y][x] == b'X'
+        for (x, &cell) in grid[y].iter().enumerate() {
+            if cell == b'X'
                 && grid[y - 1][x] == b'M'
                 && grid[y - 2][x] == b'A'
                 && grid[y - 3][x] == b'S'
@@ -1,7 +1,6 @@
+use advent_of_code::dense::GridSet;
 use rayon::prelude::*;
 
-use fxhash::{FxBuildHasher, FxHashSet};
-
 advent_of_code::solution!(6);
 
 #[derive(Clone, Copy, Debug)]
@@ -20,6 +19,10 @@ enum Direction {
 }
 
 impl Direction {
+    fn index(self) -> usize {
+        self as usize
+    }
+
     fn rotate(&mut self) {
         *self = match self {
             Direction::Up => Direction::Right,
@@ -98,20 +101,98 @@ fn look_ahead(guard: &Guard, grid: &[Vec<Cell>]) -> Option<Cell> {
     Some(get_grid_at_pos(&clone, grid))
 }
 
-fn detect_cycle(mut guard: Guard, grid: Vec<Vec<Cell>>) -> bool {
-    let mut visited = FxHashSet::default();
-    visited.insert((guard.x, guard.y, guard.direction));
+/// For every cell and direction, where a guard walking that way stops: the coordinate along the
+/// direction of travel of the last cell before an obstruction, or `None` if it walks off the map.
+struct JumpTable {
+    width: usize,
+    stops: [Vec<Option<usize>>; 4],
+}
 
-    while let Some(cell) = look_ahead(&guard, &grid) {
-        match cell {
-            Cell::Obstruction => guard.rotate(),
-            Cell::Open => guard.advance(),
+impl JumpTable {
+    fn new(grid: &[Vec<Cell>]) -> Self {
+        let (width, height) = (grid[0].len(), grid.len());
+        let mut stops: [Vec<Option<usize>>; 4] = Default::default();
+        for direction_stops in &mut stops {
+            direction_stops.resize(width * height, None);
+        }
+        let blocked = |x: usize, y: usize| matches!(grid[y][x], Cell::Obstruction);
+
+        for x in 0..width {
+            let mut stop = None;
+            for y in 0..height {
+                if blocked(x, y) {
+                    stop = Some(y + 1);
+                }
+                stops[Direction::Up.index()][y * width + x] = stop;
+            }
+            let mut stop = None;
+            for y in (0..height).rev() {
+                if blocked(x, y) {
+                    stop = y.checked_sub(1);
+                }
+                stops[Direction::Down.index()][y * width + x] = stop;
+            }
+        }
+        for y in 0..height {
+            let mut stop = None;
+            for x in 0..width {
+                if blocked(x, y) {
+                    stop = Some(x + 1);
+                }
+                stops[Direction::Left.index()][y * width + x] = stop;
+            }
+            let mut stop = None;
+            for x in (0..width).rev() {
+                if blocked(x, y) {
+                    stop = x.checked_sub(1);
+                }
+                stops[Direction::Right.index()][y * width + x] = stop;
+            }
         }
-        let pos = (guard.x, guard.y, guard.direction);
 
-        if !visited.insert(pos) {
+        Self { width, stops }
+    }
+
+    /// Where `guard` stops walking straight ahead, treating `extra` as one more obstruction.
+    fn jump(&self, guard: &Guard, (extra_x, extra_y): (usize, usize)) -> Option<(usize, usize)> {
+        let stop = self.stops[guard.direction.index()][guard.y * self.width + guard.x];
+        match guard.direction {
+            Direction::Up if extra_x == guard.x && extra_y < guard.y => {
+                Some(stop.map_or(extra_y + 1, |stop| stop.max(extra_y + 1)))
+            }
+            Direction::Down if extra_x == guard.x && extra_y > guard.y => {
+                Some(stop.map_or(extra_y - 1, |stop| stop.min(extra_y - 1)))
+            }
+            Direction::Left if extra_y == guard.y && extra_x < guard.x => {
+                Some(stop.map_or(extra_x + 1, |stop| stop.max(extra_x + 1)))
+            }
+            Direction::Right if extra_y == guard.y && extra_x > guard.x => {
+                Some(stop.map_or(extra_x - 1, |stop| stop.min(extra_x - 1)))
+            }
+            _ => stop,
+        }
+        .map(|stop| match guard.direction {
+            Direction::Up | Direction::Down => (guard.x, stop),
+            Direction::Left | Direction::Right => (stop, guard.y),
+        })
+    }
+}
+
+/// Whether the guard loops once `obstruction` is added, jumping from turn to turn. `visited`
+/// records the cell and direction of every turn and must start out empty.
+fn detect_cycle(
+    mut guard: Guard,
+    jumps: &JumpTable,
+    obstruction: (usize, usize),
+    visited: &mut GridSet,
+) -> bool {
+    while let Some((x, y)) = jumps.jump(&guard, obstruction) {
+        guard.x = x;
+        guard.y = y;
+        if !visited.insert_layer(x, y, guard.direction.index()) {
             return true;
         }
+        guard.rotate();
     }
 
     false
@@ -120,9 +201,9 @@ fn detect_cycle(mut guard: Guard, grid: Vec<Vec<Cell>>) -> bool {
 pub fn part_one(input: &str) -> Option<usize> {
     let (mut ray, grid) = parse(input);
 
-    let mut visited = FxHashSet::default();
+    let mut visited = GridSet::new(grid[0].len(), grid.len());
 
-    visited.insert((ray.x, ray.y));
+    visited.insert(ray.x, ray.y);
 
     while let Some(cell) = look_ahead(&ray, &grid) {
         match cell {
@@ -132,7 +213,7 @@ pub fn part_one(input: &str) -> Option<usize> {
             }
             Cell::Open => ray.advance(),
         }
-        visited.insert((ray.x, ray.y));
+        visited.insert(ray.x, ray.y);
     }
 
     Some(visited.len())
@@ -142,9 +223,9 @@ pub fn part_two(input: &str) -> Option<usize> {
     let (mut ray, grid) = parse(input);
 
     let initial_ray = ray.clone();
-    let mut visited = FxHashSet::with_capacity_and_hasher(100, FxBuildHasher::default());
+    let mut visited = GridSet::new(grid[0].len(), grid.len());
 
-    visited.insert((ray.x, ray.y));
+    visited.insert(ray.x, ray.y);
 
     while let Some(cell) = look_ahead(&ray, &grid) {
         match cell {
@@ -154,20 +235,25 @@ pub fn part_two(input: &str) -> Option<usize> {
             }
             Cell::Open => ray.advance(),
         }
-        visited.insert((ray.x, ray.y));
+        visited.insert(ray.x, ray.y);
     }
 
-    let positions = visited
-        .par_iter()
-        .map(|(x, y)| {
-            if initial_ray.x == *x && initial_ray.y == *y {
-                return false;
-            }
-            let mut new_grid = grid.clone();
-            new_grid[*y][*x] = Cell::Obstruction;
+    let candidates: Vec<(usize, usize)> = visited.positions().collect();
 
-            detect_cycle(initial_ray.clone(), new_grid)
-        })
+    let jumps = JumpTable::new(&grid);
+
+    let positions = candidates
+        .par_iter()
+        .map_init(
+            || GridSet::with_layers(grid[0].len(), grid.len(), 4),
+            |visited, &(x, y)| {
+                if initial_ray.x == x && initial_ray.y == y {
+                    return false;
+                }
+                visited.clear();
+                detect_cycle(initial_ray.clone(), &jumps, (x, y), visited)
+            },
+        )
         .filter(|x| *x)
         .count();
 
@@ -189,4 +275,71 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(6));
     }
+
+    /// Walks cell by cell over a copy of the grid with the obstruction added.
+    fn walk_detects_cycle(mut guard: Guard, grid: Vec<Vec<Cell>>) -> bool {
+        let mut visited = GridSet::with_layers(grid[0].len(), grid.len(), 4);
+        visited.insert_layer(guard.x, guard.y, guard.direction.index());
+
+        while let Some(cell) = look_ahead(&guard, &grid) {
+            match cell {
+                Cell::Obstruction => guard.rotate(),
+                Cell::Open => guard.advance(),
+            }
+            if !visited.insert_layer(guard.x, guard.y, guard.direction.index()) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Every obstruction that makes the guard loop, as found by `loops`.
+    fn looping_obstructions(
+        input: &str,
+        mut loops: impl FnMut(&Guard, (usize, usize)) -> bool,
+    ) -> Vec<(usize, usize)> {
+        let (guard, grid) = parse(input);
+        (0..grid.len())
+            .flat_map(|y| (0..grid[0].len()).map(move |x| (x, y)))
+            .filter(|&obstruction| obstruction != (guard.x, guard.y) && loops(&guard, obstruction))
+            .collect()
+    }
+
+    #[test]
+    fn test_jumps_match_walk() {
+        let generate = |rng: &mut advent_of_code::random::Rng| {
+            let mut grid: Vec<Vec<u8>> = (0..30)
+                .map(|_| {
+                    (0..30)
+                        .map(|_| if rng.below(12) == 0 { b'#' } else { b'.' })
+                        .collect()
+                })
+                .collect();
+            grid[15][15] = b'^';
+            grid.into_iter()
+                .map(|row| String::from_utf8(row).unwrap())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let jump = |input: &String| {
+            let grid = parse(input).1;
+            let jumps = JumpTable::new(&grid);
+            let mut visited = GridSet::with_layers(grid[0].len(), grid.len(), 4);
+            looping_obstructions(input, |guard, obstruction| {
+                visited.clear();
+                detect_cycle(guard.clone(), &jumps, obstruction, &mut visited)
+            })
+        };
+        let walk = |input: &String| {
+            let grid = parse(input).1;
+            looping_obstructions(input, |guard, (x, y)| {
+                let mut grid = grid.clone();
+                grid[y][x] = Cell::Obstruction;
+                walk_detects_cycle(guard.clone(), grid)
+            })
+        };
+
+        advent_of_code::random::cross_check(6, 5, generate, jump, walk);
+    }
 }
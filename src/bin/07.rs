@@ -1,75 +1,324 @@
+use std::{fmt::Display, ops::ControlFlow};
+
+use advent_of_code::template::{flag_value, has_flag};
+
 advent_of_code::solution!(7);
 
-fn parse(input: &str) -> Vec<(u64, Vec<u64>)> {
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Add,
+    Multiply,
+    Concat,
+    Subtract,
+}
+
+impl Operator {
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "+" => Some(Operator::Add),
+            "*" => Some(Operator::Multiply),
+            "||" => Some(Operator::Concat),
+            "-" => Some(Operator::Subtract),
+            _ => None,
+        }
+    }
+
+    /// Evaluates `left op right`, or `None` if the result is negative or overflows.
+    fn apply(self, left: u64, right: u64) -> Option<u64> {
+        match self {
+            Operator::Add => left.checked_add(right),
+            Operator::Multiply => left.checked_mul(right),
+            Operator::Concat => left
+                .checked_mul(10u64.pow(right.checked_ilog10().unwrap_or(0) + 1))?
+                .checked_add(right),
+            Operator::Subtract => left.checked_sub(right),
+        }
+    }
+
+    /// The `left` with `left op right == result`, if there is exactly one. See
+    /// [`Self::absorbs`] for when every `left` works.
+    #[inline]
+    fn undo(self, result: u64, right: u64) -> Option<u64> {
+        match self {
+            Operator::Add => result.checked_sub(right),
+            Operator::Multiply => {
+                (right != 0 && result.is_multiple_of(right)).then(|| result / right)
+            }
+            Operator::Concat => {
+                let shift = 10u64.pow(right.checked_ilog10().unwrap_or(0) + 1);
+                (result >= right && (result - right).is_multiple_of(shift)).then(|| result / shift)
+            }
+            Operator::Subtract => result.checked_add(right),
+        }
+    }
+
+    /// Whether `left op right == result` for every `left`, which is only `left * 0 == 0`.
+    #[inline]
+    fn absorbs(self, result: u64, right: u64) -> bool {
+        self == Operator::Multiply && right == 0 && result == 0
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operator::Add => write!(f, "+"),
+            Operator::Multiply => write!(f, "*"),
+            Operator::Concat => write!(f, "||"),
+            Operator::Subtract => write!(f, "-"),
+        }
+    }
+}
+
+// Operators are tried in order, so the ones that are hardest to undo go first to prune sooner.
+const PART_ONE: &[Operator] = &[Operator::Multiply, Operator::Add];
+const PART_TWO: &[Operator] = &[Operator::Concat, Operator::Multiply, Operator::Add];
+
+#[derive(Debug)]
+struct Equation {
+    result: u64,
+    values: Vec<u64>,
+}
+
+impl Equation {
+    /// Evaluates the values left to right with `operators` between them, or `None` if a step
+    /// goes negative or overflows.
+    fn evaluate(&self, operators: &[Operator]) -> Option<u64> {
+        operators
+            .iter()
+            .zip(&self.values[1..])
+            .try_fold(self.values[0], |left, (operator, &right)| {
+                operator.apply(left, right)
+            })
+    }
+
+    /// Whether some assignment of `operators` makes the equation true. This is [`Self::search`]
+    /// without tracking the operators chosen, which is all the parts need, and the puzzle's own
+    /// operator sets get a version with the operators fixed at compile time.
+    fn is_solvable(&self, operators: &[Operator]) -> bool {
+        /// Whether some assignment of `operators` evaluates `left` and then `values` without
+        /// going negative or overflowing.
+        fn evaluates(left: u64, values: &[u64], operators: &[Operator]) -> bool {
+            match values {
+                [] => true,
+                [right, rest @ ..] => operators.iter().any(|operator| {
+                    operator
+                        .apply(left, *right)
+                        .is_some_and(|left| evaluates(left, rest, operators))
+                }),
+            }
+        }
+
+        fn undo(result: u64, values: &[u64], operators: &[Operator]) -> bool {
+            match values {
+                [] => false,
+                [first] => result == *first,
+                [rest @ .., last] => operators.iter().any(|operator| {
+                    if operator.absorbs(result, *last) {
+                        return evaluates(rest[0], &rest[1..], operators);
+                    }
+                    operator
+                        .undo(result, *last)
+                        .is_some_and(|left| undo(left, rest, operators))
+                }),
+            }
+        }
+
+        fn undo_default<const CONCAT: bool>(result: u64, values: &[u64]) -> bool {
+            match values {
+                [] => false,
+                [first] => result == *first,
+                [rest @ .., last] => {
+                    let undone = |operator: Operator| {
+                        if operator.absorbs(result, *last) {
+                            let operators = if CONCAT { PART_TWO } else { PART_ONE };
+                            return evaluates(rest[0], &rest[1..], operators);
+                        }
+                        operator
+                            .undo(result, *last)
+                            .is_some_and(|left| undo_default::<CONCAT>(left, rest))
+                    };
+                    (CONCAT && undone(Operator::Concat))
+                        || undone(Operator::Multiply)
+                        || undone(Operator::Add)
+                }
+            }
+        }
+
+        if operators == PART_ONE {
+            undo_default::<false>(self.result, &self.values)
+        } else if operators == PART_TWO {
+            undo_default::<true>(self.result, &self.values)
+        } else {
+            undo(self.result, &self.values, operators)
+        }
+    }
+
+    /// Calls `visit` with every assignment of `operators` that makes the equation true, in
+    /// reverse order, until it breaks. Operators are evaluated left to right, so the search
+    /// undoes them from the right, which rules out most branches straight away. A `* 0` that
+    /// has to make 0 can't be undone, so the values before it are evaluated forwards instead.
+    fn search(
+        &self,
+        operators: &[Operator],
+        visit: &mut impl FnMut(&[Operator]) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        /// Visits every assignment of `operators` that evaluates `left` and then `values`
+        /// without going negative or overflowing, with `prefix` holding the operators so far.
+        fn evaluate(
+            left: u64,
+            values: &[u64],
+            operators: &[Operator],
+            prefix: &mut Vec<Operator>,
+            chosen: &mut Vec<Operator>,
+            visit: &mut impl FnMut(&[Operator]) -> ControlFlow<()>,
+        ) -> ControlFlow<()> {
+            let Some((&right, rest)) = values.split_first() else {
+                let len = chosen.len();
+                chosen.extend(prefix.iter().rev());
+                let flow = visit(chosen);
+                chosen.truncate(len);
+                return flow;
+            };
+
+            for &operator in operators {
+                if let Some(left) = operator.apply(left, right) {
+                    prefix.push(operator);
+                    let flow = evaluate(left, rest, operators, prefix, chosen, visit);
+                    prefix.pop();
+                    flow?;
+                }
+            }
+            ControlFlow::Continue(())
+        }
+
+        fn undo(
+            result: u64,
+            values: &[u64],
+            operators: &[Operator],
+            chosen: &mut Vec<Operator>,
+            visit: &mut impl FnMut(&[Operator]) -> ControlFlow<()>,
+        ) -> ControlFlow<()> {
+            let Some((&last, rest)) = values.split_last() else {
+                return ControlFlow::Continue(());
+            };
+            if rest.is_empty() {
+                return if result == last {
+                    visit(chosen)
+                } else {
+                    ControlFlow::Continue(())
+                };
+            }
+
+            for &operator in operators {
+                if operator.absorbs(result, last) {
+                    chosen.push(operator);
+                    let mut prefix = Vec::with_capacity(rest.len());
+                    let flow = evaluate(rest[0], &rest[1..], operators, &mut prefix, chosen, visit);
+                    chosen.pop();
+                    flow?;
+                } else if let Some(left) = operator.undo(result, last) {
+                    chosen.push(operator);
+                    let flow = undo(left, rest, operators, chosen, visit);
+                    chosen.pop();
+                    flow?;
+                }
+            }
+            ControlFlow::Continue(())
+        }
+
+        let mut chosen = Vec::with_capacity(self.values.len());
+        undo(self.result, &self.values, operators, &mut chosen, visit)
+    }
+
+    /// One assignment of `operators` that makes the equation true.
+    fn witness(&self, operators: &[Operator]) -> Option<Vec<Operator>> {
+        let mut witness = None;
+        let _ = self.search(operators, &mut |reversed| {
+            witness = Some(reversed.iter().rev().copied().collect());
+            ControlFlow::Break(())
+        });
+        witness
+    }
+
+    /// The number of assignments of `operators` that make the equation true.
+    fn count_solutions(&self, operators: &[Operator]) -> usize {
+        let mut count = 0;
+        let _ = self.search(operators, &mut |_| {
+            count += 1;
+            ControlFlow::Continue(())
+        });
+        count
+    }
+
+    fn expression(&self, operators: &[Operator]) -> String {
+        let mut expression = format!("{} = {}", self.result, self.values[0]);
+        for (operator, value) in operators.iter().zip(&self.values[1..]) {
+            expression.push_str(&format!(" {operator} {value}"));
+        }
+        expression
+    }
+}
+
+fn parse(input: &str) -> Vec<Equation> {
     input
         .trim_end()
         .lines()
         .map(|line| {
             let (result, nums) = line.split_once(": ").unwrap();
 
-            (
-                result.parse().unwrap(),
-                nums.split_ascii_whitespace().flat_map(str::parse).collect(),
-            )
+            Equation {
+                result: result.parse().unwrap(),
+                values: nums.split_ascii_whitespace().flat_map(str::parse).collect(),
+            }
         })
         .collect()
 }
 
-fn check(current: u64, values: &[u64], should_concat: bool) -> bool {
-    if values.len() == 1 {
-        return current == values[0];
-    }
+/// Sums the results of the equations that `operators` can make true, printing a witness and
+/// the number of solutions for each with `--witness`. `--operators +,-` swaps in another set.
+fn calibrate(input: &str, operators: &[Operator]) -> u64 {
+    let show = has_flag("--witness");
+    let chosen: Option<Vec<Operator>> = flag_value("--operators").map(|symbols| {
+        symbols
+            .split(',')
+            .map(|symbol| Operator::from_symbol(symbol).expect("Invalid operator"))
+            .collect()
+    });
+    let operators = chosen.as_deref().unwrap_or(operators);
 
-    if let Some((value, values)) = values.split_last() {
-        if should_concat {
-            let num_digits = 10u64.pow(value.checked_ilog10().unwrap_or(0) + 1);
-            if (current.wrapping_sub(*value)) % num_digits == 0
-                && check(current / num_digits, values, should_concat)
-            {
-                return true;
+    parse(input)
+        .iter()
+        .filter(|equation| {
+            if !show {
+                return equation.is_solvable(operators);
             }
-        }
 
-        if current % value == 0 && check(current / value, values, should_concat) {
-            return true;
-        }
-        if current >= *value && check(current - value, values, should_concat) {
-            return true;
-        }
-    }
-    false
+            let witness = equation.witness(operators);
+            match &witness {
+                Some(witness) => {
+                    // the search works backwards from the result, so check forwards too
+                    assert_eq!(equation.evaluate(witness), Some(equation.result));
+                    println!(
+                        "{} (solutions: {})",
+                        equation.expression(witness),
+                        equation.count_solutions(operators)
+                    );
+                }
+                None => println!("{}: no solution", equation.result),
+            }
+            witness.is_some()
+        })
+        .map(|equation| equation.result)
+        .sum()
 }
 
 pub fn part_one(input: &str) -> Option<u64> {
-    let calibrations = parse(input);
-
-    Some(
-        calibrations
-            .iter()
-            .map(|(result, numbers)| {
-                if check(*result, numbers, false) {
-                    return result;
-                }
-                &0
-            })
-            .sum(),
-    )
+    Some(calibrate(input, PART_ONE))
 }
 
 pub fn part_two(input: &str) -> Option<u64> {
-    let calibrations = parse(input);
-
-    Some(
-        calibrations
-            .iter()
-            .map(|(result, numbers)| {
-                if check(*result, numbers, true) {
-                    return result;
-                }
-                &0
-            })
-            .sum(),
-    )
+    Some(calibrate(input, PART_TWO))
 }
 
 #[cfg(test)]
@@ -87,4 +336,127 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(11387));
     }
+
+    #[test]
+    fn test_witnesses() {
+        let equations = parse(&advent_of_code::template::read_file("examples", DAY));
+
+        let witnesses: Vec<String> = equations
+            .iter()
+            .filter_map(|equation| {
+                let witness = equation.witness(PART_TWO)?;
+                assert_eq!(equation.evaluate(&witness), Some(equation.result));
+                Some(equation.expression(&witness))
+            })
+            .collect();
+        assert_eq!(
+            witnesses,
+            [
+                "190 = 10 * 19",
+                "3267 = 81 + 40 * 27",
+                "156 = 15 || 6",
+                "7290 = 6 * 8 || 6 * 15",
+                "192 = 17 || 8 + 14",
+                "292 = 11 + 6 * 16 + 20",
+            ]
+        );
+
+        let counts: Vec<usize> = equations
+            .iter()
+            .map(|equation| equation.count_solutions(PART_ONE))
+            .collect();
+        assert_eq!(counts, [1, 2, 0, 0, 0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_other_operators() {
+        let equation = Equation {
+            result: 7,
+            values: vec![10, 5, 2],
+        };
+        let operators = &[Operator::Add, Operator::Subtract];
+
+        assert_eq!(
+            equation.witness(operators),
+            Some(vec![Operator::Subtract, Operator::Add])
+        );
+        assert_eq!(equation.count_solutions(operators), 1);
+        assert_eq!(equation.witness(PART_ONE), None);
+    }
+
+    #[test]
+    fn test_multiply_by_zero() {
+        let equation = Equation {
+            result: 0,
+            values: vec![5, 0],
+        };
+        assert!(equation.is_solvable(PART_ONE));
+        assert_eq!(equation.witness(PART_ONE), Some(vec![Operator::Multiply]));
+        assert_eq!(equation.count_solutions(PART_ONE), 1);
+
+        // 3 - 4 goes negative, so only 3 + 4 * 0 and 3 * 4 * 0 are left to multiply by zero
+        let equation = Equation {
+            result: 0,
+            values: vec![3, 4, 0],
+        };
+        assert_eq!(
+            equation.count_solutions(&[Operator::Subtract, Operator::Multiply, Operator::Add]),
+            2
+        );
+    }
+
+    const ALL: &[Operator] = &[
+        Operator::Add,
+        Operator::Multiply,
+        Operator::Concat,
+        Operator::Subtract,
+    ];
+
+    /// The result of every assignment of `operators`, in order.
+    fn every_result(values: &[u64], operators: &[Operator]) -> Vec<Option<u64>> {
+        let equation = Equation {
+            result: 0,
+            values: values.to_vec(),
+        };
+        let gaps = values.len() as u32 - 1;
+        (0..operators.len().pow(gaps))
+            .map(|mut code| {
+                let chosen: Vec<Operator> = (0..gaps)
+                    .map(|_| {
+                        let operator = operators[code % operators.len()];
+                        code /= operators.len();
+                        operator
+                    })
+                    .collect();
+                equation.evaluate(&chosen)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_counts_against_brute_force() {
+        let generate = |rng: &mut advent_of_code::random::Rng| {
+            let values: Vec<u64> = (0..2 + rng.below(4)).map(|_| rng.below(20)).collect();
+            let results: Vec<u64> = every_result(&values, ALL).into_iter().flatten().collect();
+            let result = results[rng.below(results.len() as u64) as usize];
+            Equation { result, values }
+        };
+        let search = |equation: &Equation| {
+            [ALL, PART_ONE, PART_TWO].map(|operators| {
+                let count = equation.count_solutions(operators);
+                assert_eq!(equation.is_solvable(operators), count > 0);
+                count
+            })
+        };
+        let brute_force = |equation: &Equation| {
+            [ALL, PART_ONE, PART_TWO].map(|operators| {
+                every_result(&equation.values, operators)
+                    .into_iter()
+                    .filter(|&result| result == Some(equation.result))
+                    .count()
+            })
+        };
+
+        advent_of_code::random::cross_check(7, 200, generate, search, brute_force);
+    }
 }
@@ -1,3 +1,7 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use advent_of_code::template::has_flag;
+
 advent_of_code::solution!(9);
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -38,6 +42,125 @@ fn checksum(memory: &[FileBlock]) -> u64 {
         .sum()
 }
 
+/// A run of `len` blocks starting at block `start`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Span {
+    start: usize,
+    len: usize,
+}
+
+/// The disk as runs of blocks: `files[id]` is where file `id` lives, `gaps` the free runs
+/// between them.
+#[derive(Debug)]
+struct Disk {
+    files: Vec<Span>,
+    gaps: Vec<Span>,
+}
+
+impl Disk {
+    fn new(input: &str) -> Self {
+        let mut files = Vec::new();
+        let mut gaps = Vec::new();
+        let mut start = 0;
+        for (index, block_size) in input.trim_end().bytes().enumerate() {
+            let len = (block_size - b'0') as usize;
+            let span = Span { start, len };
+            if index % 2 == 0 {
+                files.push(span);
+            } else if len > 0 {
+                gaps.push(span);
+            }
+            start += len;
+        }
+
+        Self { files, gaps }
+    }
+
+    /// Moves every file, highest id first, into the leftmost gap it fits in, if that is left of
+    /// where it is. Gaps are kept in one min-heap of start positions per length, so each file
+    /// only compares the front of nine heaps.
+    fn compact_files(&mut self) {
+        let mut gaps_by_len: [BinaryHeap<Reverse<usize>>; 10] = Default::default();
+        for gap in &self.gaps {
+            gaps_by_len[gap.len].push(Reverse(gap.start));
+        }
+
+        for file in self.files.iter_mut().rev() {
+            let best = (file.len..gaps_by_len.len())
+                .filter_map(|len| {
+                    let &Reverse(start) = gaps_by_len[len].peek()?;
+                    Some((start, len))
+                })
+                .filter(|&(start, _)| start < file.start)
+                .min();
+
+            if let Some((start, len)) = best {
+                gaps_by_len[len].pop();
+                file.start = start;
+                if len > file.len {
+                    gaps_by_len[len - file.len].push(Reverse(start + file.len));
+                }
+            }
+        }
+
+        self.gaps = self
+            .files_by_position()
+            .windows(2)
+            .filter_map(|pair| {
+                let end = pair[0].start + pair[0].len;
+                (end < pair[1].start).then(|| Span {
+                    start: end,
+                    len: pair[1].start - end,
+                })
+            })
+            .collect();
+    }
+
+    fn files_by_position(&self) -> Vec<Span> {
+        let mut files = self.files.clone();
+        files.sort_unstable_by_key(|file| file.start);
+        files
+    }
+
+    fn checksum(&self) -> u64 {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(id, file)| {
+                // sum of the positions start..start + len
+                let positions = file.start * file.len + file.len * file.len.saturating_sub(1) / 2;
+                (id * positions) as u64
+            })
+            .sum()
+    }
+
+    /// The disk block by block, as [`parse`] lays it out.
+    fn blocks(&self) -> Vec<FileBlock> {
+        let end = self
+            .files
+            .iter()
+            .map(|file| file.start + file.len)
+            .max()
+            .unwrap_or(0);
+        let mut blocks = vec![FileBlock::Empty; end];
+        for (id, file) in self.files.iter().enumerate() {
+            blocks[file.start..file.start + file.len].fill(FileBlock::File(id as u32));
+        }
+        blocks
+    }
+}
+
+/// Draws blocks like the puzzle does, with `#` for file ids above 9.
+fn render(blocks: &[FileBlock]) -> String {
+    blocks
+        .iter()
+        .map(|block| match block {
+            FileBlock::File(id) => char::from_digit(*id, 10).unwrap_or('#'),
+            FileBlock::Empty => '.',
+        })
+        .collect()
+}
+
 pub fn part_one(input: &str) -> Option<u64> {
     let mut memory_bank = parse(input);
 
@@ -67,70 +190,16 @@ pub fn part_one(input: &str) -> Option<u64> {
 }
 
 pub fn part_two(input: &str) -> Option<u64> {
-    let mut memory_bank = parse(input);
-
-    let mut front;
-    let mut back = memory_bank.len() - 1;
-
-    loop {
-        front = 0;
-        //get last none empty block from right
-        while memory_bank[back] == FileBlock::Empty {
-            back -= 1;
-        }
-
-        if back == 0 {
-            break;
-        }
-
-        let mut file_size = 1;
-        let file = memory_bank[back];
-        loop {
-            if back - file_size == 0 {
-                break;
-            }
-            if memory_bank[back - file_size] != file {
-                break;
-            }
-            file_size += 1;
-        }
+    let mut disk = Disk::new(input);
 
-        let mut empty_size = 0;
-        loop {
-            //get first empty space from left
-            while memory_bank[front] != FileBlock::Empty {
-                front += 1;
-            }
-            if front + empty_size > back {
-                break;
-            }
-
-            // find length of empty space
-            loop {
-                if memory_bank[front + empty_size] != FileBlock::Empty {
-                    break;
-                }
-                empty_size += 1;
-            }
-            if empty_size >= file_size {
-                break;
-            }
-            front += empty_size;
-            empty_size = 0;
-        }
-
-        if file_size > empty_size {
-            back -= file_size;
-            continue;
-        }
+    disk.compact_files();
+    debug_assert_eq!(checksum(&disk.blocks()), disk.checksum());
 
-        for i in 0..file_size {
-            memory_bank[front + i] = memory_bank[back - i];
-            memory_bank[back - i] = FileBlock::Empty;
-        }
+    if has_flag("--render") {
+        println!("{}", render(&disk.blocks()));
     }
 
-    Some(checksum(&memory_bank))
+    Some(disk.checksum())
 }
 
 #[cfg(test)]
@@ -148,4 +217,80 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(2858));
     }
+
+    #[test]
+    fn test_disk_blocks_match_parse() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let disk = Disk::new(&input);
+        let blocks = parse(&input);
+        assert_eq!(disk.blocks(), blocks[..disk.blocks().len()]);
+        assert_eq!(
+            render(&blocks),
+            "00...111...2...333.44.5555.6666.777.888899"
+        );
+        assert_eq!(disk.checksum(), checksum(&blocks));
+    }
+
+    /// Whole-file compaction on the block vector, scanning from the left for every file.
+    fn compact_blocks(blocks: &mut [FileBlock], files: usize) {
+        for id in (0..files as u32).rev() {
+            let file = FileBlock::File(id);
+            let start = blocks.iter().position(|&block| block == file).unwrap();
+            let len = blocks[start..]
+                .iter()
+                .take_while(|&&block| block == file)
+                .count();
+
+            let mut run = 0;
+            for index in 0..start {
+                run = if blocks[index] == FileBlock::Empty {
+                    run + 1
+                } else {
+                    0
+                };
+                if run == len {
+                    let gap = index + 1 - len;
+                    blocks[gap..=index].fill(file);
+                    blocks[start..start + len].fill(FileBlock::Empty);
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compact_files_matches_blocks() {
+        let generate = |rng: &mut advent_of_code::random::Rng| -> String {
+            (0..2 * rng.below(40) + 1)
+                .map(|index| {
+                    let min = if index % 2 == 0 { 1 } else { 0 };
+                    char::from_digit((min + rng.below(10 - min)) as u32, 10).unwrap()
+                })
+                .collect()
+        };
+        let compact = |input: &String| {
+            let mut disk = Disk::new(input);
+            disk.compact_files();
+            disk.checksum()
+        };
+        let reference = |input: &String| {
+            let mut blocks = parse(input);
+            compact_blocks(&mut blocks, Disk::new(input).files.len());
+            checksum(&blocks)
+        };
+
+        advent_of_code::random::cross_check(9, 50, generate, compact, reference);
+    }
+
+    #[test]
+    fn test_compact_files_render() {
+        let mut disk = Disk::new(&advent_of_code::template::read_file("examples", DAY));
+        disk.compact_files();
+        assert_eq!(
+            render(&disk.blocks()),
+            "00992111777.44.333....5555.6666.....8888"
+        );
+        assert_eq!(checksum(&disk.blocks()), disk.checksum());
+        assert_eq!(disk.gaps[0], Span { start: 11, len: 1 });
+    }
 }
@@ -1,6 +1,6 @@
 use std::collections::VecDeque;
 
-use fxhash::FxHashSet;
+use advent_of_code::dense::GridSet;
 
 advent_of_code::solution!(10);
 
@@ -63,6 +63,7 @@ pub fn part_one(input: &str) -> Option<u32> {
     let grid = Grid::new(input);
 
     let starts = grid.find_starts();
+    let mut seen = GridSet::new(grid.width as usize, grid.height as usize);
 
     Some(
         starts
@@ -70,19 +71,19 @@ pub fn part_one(input: &str) -> Option<u32> {
             .map(|start| {
                 let mut num_paths = 0;
                 let mut queue = VecDeque::new();
-                let mut seen = FxHashSet::default();
+                seen.clear();
                 queue.push_back(*start);
 
                 while let Some(current_location) = queue.pop_front() {
                     let (x, y) = current_location;
 
-                    if seen.contains(&(x, y)) {
+                    if seen.contains(x as usize, y as usize) {
                         continue;
                     }
                     let current_value = grid.get(x, y);
                     if current_value == 9 {
                         num_paths += 1;
-                        seen.insert((x, y));
+                        seen.insert(x as usize, y as usize);
                     } else {
                         for (adjacent_value, next_x, next_y) in grid.get_adjacent(x, y) {
                             if adjacent_value - current_value == 1 {
@@ -1,17 +1,17 @@
-use fxhash::FxHashMap;
-use nom::{
-    bytes::complete::tag, character::complete::digit1, combinator::map_res, multi::separated_list1,
-    IResult,
+use std::fmt::Display;
+
+use advent_of_code::{
+    parse::combinators::{spaced, unsigned},
+    template::flag_value,
 };
+use fxhash::FxHashMap;
+use nom::IResult;
+use num_bigint::BigUint;
 
 advent_of_code::solution!(11);
 
-fn number(input: &str) -> IResult<&str, u64> {
-    map_res(digit1, str::parse)(input)
-}
-
 fn engraved_stones(input: &str) -> IResult<&str, Vec<u64>> {
-    separated_list1(tag(" "), number)(input)
+    spaced(unsigned)(input)
 }
 
 fn apply_rules(stone: u64) -> Vec<u64> {
@@ -21,7 +21,7 @@ fn apply_rules(stone: u64) -> Vec<u64> {
     }
     let num_digits = stone.checked_ilog10().unwrap_or(0) + 1;
     // even digits split number
-    if num_digits % 2 == 0 {
+    if num_digits.is_multiple_of(2) {
         let magnitude = 10u64.pow(num_digits / 2);
         let left_half = stone / magnitude;
         let right_half = stone - (left_half * magnitude);
@@ -30,39 +30,242 @@ fn apply_rules(stone: u64) -> Vec<u64> {
     vec![stone * 2024]
 }
 
-pub fn part_one(input: &str) -> Option<usize> {
-    let (_, mut rocks) = engraved_stones(input).unwrap();
+/// A per-stone count that reports overflow instead of wrapping.
+trait Tally: Clone + Default {
+    /// Adds `other`, returning `false` if the result doesn't fit.
+    fn try_add(&mut self, other: &Self) -> bool;
+    fn is_zero(&self) -> bool;
+}
+
+impl Tally for u64 {
+    fn try_add(&mut self, other: &Self) -> bool {
+        self.checked_add(*other).map(|sum| *self = sum).is_some()
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+}
+
+impl Tally for u128 {
+    fn try_add(&mut self, other: &Self) -> bool {
+        self.checked_add(*other).map(|sum| *self = sum).is_some()
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+}
 
-    for _ in 0..25 {
-        rocks = rocks.into_iter().flat_map(apply_rules).collect();
+impl Tally for BigUint {
+    fn try_add(&mut self, other: &Self) -> bool {
+        *self += other;
+        true
     }
 
-    Some(rocks.len())
+    fn is_zero(&self) -> bool {
+        *self == BigUint::ZERO
+    }
+}
+
+/// A number of stones, in the narrowest type that holds it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Count {
+    Narrow(u64),
+    Wide(u128),
+    Big(BigUint),
+}
+
+impl Count {
+    fn to_usize(&self) -> Option<usize> {
+        match self {
+            Count::Narrow(count) => (*count).try_into().ok(),
+            Count::Wide(count) => (*count).try_into().ok(),
+            Count::Big(count) => count.try_into().ok(),
+        }
+    }
+}
+
+impl Display for Count {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Count::Narrow(count) => write!(f, "{count}"),
+            Count::Wide(count) => write!(f, "{count}"),
+            Count::Big(count) => write!(f, "{count}"),
+        }
+    }
+}
+
+/// How often each stone occurs, indexed by the ids handed out by [`Blinker`].
+#[derive(Debug, Clone, PartialEq)]
+enum Counts {
+    Narrow(Vec<u64>),
+    Wide(Vec<u128>),
+    Big(Vec<BigUint>),
+}
+
+impl Counts {
+    /// Moves to the next wider count type.
+    fn widen(self) -> Self {
+        match self {
+            Counts::Narrow(counts) => Counts::Wide(counts.into_iter().map(u128::from).collect()),
+            Counts::Wide(counts) => Counts::Big(counts.into_iter().map(BigUint::from).collect()),
+            Counts::Big(_) => self,
+        }
+    }
+
+    fn total(&self) -> Count {
+        match self {
+            Counts::Narrow(counts) => {
+                let total: u128 = counts.iter().map(|&count| u128::from(count)).sum();
+                u64::try_from(total).map_or(Count::Wide(total), Count::Narrow)
+            }
+            Counts::Wide(counts) => counts
+                .iter()
+                .try_fold(0u128, |total, &count| total.checked_add(count))
+                .map_or_else(
+                    || Count::Big(counts.iter().map(|&count| BigUint::from(count)).sum()),
+                    Count::Wide,
+                ),
+            Counts::Big(counts) => Count::Big(counts.iter().sum()),
+        }
+    }
+
+    /// Number of different engravings among the stones.
+    fn distinct(&self) -> usize {
+        fn non_zero<C: Tally>(counts: &[C]) -> usize {
+            counts.iter().filter(|count| !count.is_zero()).count()
+        }
+
+        match self {
+            Counts::Narrow(counts) => non_zero(counts),
+            Counts::Wide(counts) => non_zero(counts),
+            Counts::Big(counts) => non_zero(counts),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct BlinkStats {
+    total: Count,
+    distinct: usize,
+}
+
+/// Simulates blinking with stones numbered densely in the order they're first seen. The rules
+/// for each stone are applied once and remembered across calls to [`Blinker::blink`].
+#[derive(Debug, Default)]
+struct Blinker {
+    ids: FxHashMap<u64, usize>,
+    transitions: Vec<Option<(usize, Option<usize>)>>,
+    stones: Vec<u64>,
+}
+
+impl Blinker {
+    fn id(&mut self, stone: u64) -> usize {
+        *self.ids.entry(stone).or_insert_with(|| {
+            self.stones.push(stone);
+            self.transitions.push(None);
+            self.stones.len() - 1
+        })
+    }
+
+    /// The ids of the one or two stones `id` turns into.
+    fn transition(&mut self, id: usize) -> (usize, Option<usize>) {
+        if let Some(next) = self.transitions[id] {
+            return next;
+        }
+        let stones = apply_rules(self.stones[id]);
+        let next = (
+            self.id(stones[0]),
+            stones.get(1).map(|&stone| self.id(stone)),
+        );
+        self.transitions[id] = Some(next);
+        next
+    }
+
+    /// One blink, or `None` if a count overflows `C`.
+    fn step<C: Tally>(&mut self, counts: &[C]) -> Option<Vec<C>> {
+        let mut next: Vec<C> = vec![C::default(); self.stones.len()];
+        for (id, count) in counts.iter().enumerate() {
+            if count.is_zero() {
+                continue;
+            }
+            let (first, second) = self.transition(id);
+            for produced in std::iter::once(first).chain(second) {
+                if produced >= next.len() {
+                    next.resize(self.stones.len(), C::default());
+                }
+                if !next[produced].try_add(count) {
+                    return None;
+                }
+            }
+        }
+        Some(next)
+    }
+
+    /// Blinks `blinks` times at `stones`, widening the counts whenever they would overflow.
+    /// Returns the statistics before the first blink and after every blink.
+    fn blink(&mut self, stones: &[u64], blinks: usize) -> Vec<BlinkStats> {
+        let mut initial = vec![];
+        for &stone in stones {
+            let id = self.id(stone);
+            if id >= initial.len() {
+                initial.resize(id + 1, 0);
+            }
+            initial[id] += 1;
+        }
+
+        let mut counts = Counts::Narrow(initial);
+        let mut stats = Vec::with_capacity(blinks + 1);
+        stats.push(BlinkStats {
+            total: counts.total(),
+            distinct: counts.distinct(),
+        });
+
+        for _ in 0..blinks {
+            counts = loop {
+                let next = match &counts {
+                    Counts::Narrow(counts) => self.step(counts).map(Counts::Narrow),
+                    Counts::Wide(counts) => self.step(counts).map(Counts::Wide),
+                    Counts::Big(counts) => self.step(counts).map(Counts::Big),
+                };
+                match next {
+                    Some(next) => break next,
+                    None => counts = counts.widen(),
+                }
+            };
+            stats.push(BlinkStats {
+                total: counts.total(),
+                distinct: counts.distinct(),
+            });
+        }
+
+        stats
+    }
+}
+
+pub fn part_one(input: &str) -> Option<usize> {
+    let (_, rocks) = engraved_stones(input).unwrap();
+
+    Blinker::default().blink(&rocks, 25)[25].total.to_usize()
 }
 
 pub fn part_two(input: &str) -> Option<usize> {
     let (_, rocks) = engraved_stones(input).unwrap();
 
-    let mut rock_collection = FxHashMap::default();
-    rocks.iter().for_each(|rock| {
-        rock_collection.insert(*rock, 1u64);
-    });
-
-    for _ in 0..75 {
-        let mut temp_collection = FxHashMap::default();
-        for (&stone, &count) in rock_collection.iter() {
-            let rules_collection = apply_rules(stone);
-            for stone in rules_collection {
-                temp_collection
-                    .entry(stone)
-                    .and_modify(|x| *x += count)
-                    .or_insert(count);
-            }
+    let mut blinker = Blinker::default();
+
+    if let Some(blinks) = flag_value("--blinks") {
+        let blinks = blinks.parse().expect("--blinks takes a number");
+        for (blink, stats) in blinker.blink(&rocks, blinks).iter().enumerate() {
+            println!(
+                "blink {blink}: {} stones, {} distinct",
+                stats.total, stats.distinct
+            );
         }
-        rock_collection = temp_collection;
     }
 
-    Some(rock_collection.values().map(|x| *x as usize).sum())
+    blinker.blink(&rocks, 75)[75].total.to_usize()
 }
 
 #[cfg(test)]
@@ -80,4 +283,48 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(65601038650482));
     }
+
+    #[test]
+    fn test_blink_matches_naive_simulation() {
+        let mut stones = vec![125, 17];
+        let stats = Blinker::default().blink(&stones, 25);
+
+        for expected in stats {
+            assert_eq!(expected.total, Count::Narrow(stones.len() as u64));
+            let mut distinct = stones.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+            assert_eq!(expected.distinct, distinct.len());
+
+            stones = stones.into_iter().flat_map(apply_rules).collect();
+        }
+    }
+
+    #[test]
+    fn test_blink_widens_counts() {
+        let mut blinker = Blinker::default();
+        let stats = blinker.blink(&[125, 17], 400);
+        assert!(matches!(stats[100].total, Count::Narrow(_)));
+        assert!(matches!(stats[200].total, Count::Wide(_)));
+        assert!(matches!(stats[400].total, Count::Big(_)));
+
+        // counting with big integers from the start gives the same totals
+        let mut counts = Counts::Narrow(vec![0; blinker.stones.len()])
+            .widen()
+            .widen();
+        if let Counts::Big(counts) = &mut counts {
+            counts[blinker.ids[&125]] += 1u32;
+            counts[blinker.ids[&17]] += 1u32;
+        }
+        for stats in &stats[1..] {
+            counts = match counts {
+                Counts::Big(counts) => Counts::Big(blinker.step(&counts).unwrap()),
+                _ => unreachable!(),
+            };
+            let Count::Big(expected) = counts.total() else {
+                unreachable!()
+            };
+            assert_eq!(stats.total.to_string(), expected.to_string());
+        }
+    }
 }
@@ -1,9 +1,6 @@
-use std::{
-    collections::{HashSet, VecDeque},
-    hash::BuildHasherDefault,
-};
+use std::fmt::Display;
 
-use fxhash::{FxHashSet, FxHasher};
+use advent_of_code::template::has_flag;
 
 advent_of_code::solution!(12);
 
@@ -27,202 +24,207 @@ impl Grid {
         }
     }
 
-    fn count_edges(&self, x: i32, y: i32) -> u32 {
-        let mut count = 0;
-        if x == 0 || x == self.width - 1 {
-            count += 1;
-        }
-        if y == 0 || y == self.height - 1 {
-            count += 1;
-        }
-
-        let current_value = self.get(x, y);
-
-        for (value, _adjacent_x, _adjacent_y) in self.get_adjacent(x, y) {
-            if current_value != value {
-                count += 1;
-            }
-        }
-
-        count
-    }
-
     fn get(&self, x: i32, y: i32) -> u8 {
         self.grid[y as usize][x as usize]
     }
 
-    fn get_safe(&self, x: i32, y: i32) -> Option<u8> {
-        self.grid.get(y as usize)?.get(x as usize).copied()
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height
     }
-    fn get_adjacent(&self, x: i32, y: i32) -> impl Iterator<Item = (u8, i32, i32)> + '_ {
-        let width = self.width;
-        let height = self.height;
-        [(1, 0), (-1, 0), (0, 1), (0, -1)]
-            .into_iter()
-            .filter(move |(delta_x, delta_y)| {
-                x + delta_x >= 0 && x + delta_x < width && y + delta_y >= 0 && y + delta_y < height
-            })
-            .map(move |(delta_x, delta_y)| {
-                let new_x = x + delta_x;
-                let new_y = y + delta_y;
-                (self.get(new_x, new_y), new_x, new_y)
-            })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BoundingBox {
+    min_x: usize,
+    min_y: usize,
+    max_x: usize,
+    max_y: usize,
+}
+
+impl BoundingBox {
+    fn extend(&mut self, x: usize, y: usize) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
     }
 }
 
-fn trace_plot(
+/// A connected group of plots growing the same crop.
+#[derive(Debug, Clone, PartialEq)]
+struct Region {
     crop: u8,
-    start: (i32, i32),
-    grid: &Grid,
-    visited: &mut HashSet<(i32, i32), BuildHasherDefault<FxHasher>>,
-) -> (u32, u32) {
-    let mut area = 0;
-    let mut perimeter = 0;
-    let mut queue = VecDeque::new();
-
-    queue.push_back(start);
-
-    while let Some(point) = queue.pop_front() {
-        if !visited.insert(point) {
-            continue;
-        }
+    area: u32,
+    perimeter: u32,
+    /// A polygon has as many sides as corners, so this counts corners.
+    sides: u32,
+    bounds: BoundingBox,
+}
 
-        let (x, y) = point;
+impl Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: area {}, perimeter {}, {} sides, x {}..={}, y {}..={}",
+            self.crop as char,
+            self.area,
+            self.perimeter,
+            self.sides,
+            self.bounds.min_x,
+            self.bounds.max_x,
+            self.bounds.min_y,
+            self.bounds.max_y
+        )
+    }
+}
 
-        area += 1;
+/// The grid's regions, with every plot labelled by the index of its region.
+#[derive(Debug)]
+struct Regions {
+    width: i32,
+    height: i32,
+    labels: Vec<usize>,
+    regions: Vec<Region>,
+}
 
-        perimeter += grid.count_edges(x, y);
+impl Regions {
+    fn new(grid: &Grid) -> Self {
+        let mut regions = Self {
+            width: grid.width,
+            height: grid.height,
+            labels: vec![usize::MAX; (grid.width * grid.height) as usize],
+            regions: vec![],
+        };
+        regions.label(grid);
+        regions.measure();
+        regions
+    }
 
-        for (value, bordering_x, bordering_y) in grid.get_adjacent(x, y) {
-            if value == crop {
-                queue.push_back((bordering_x, bordering_y));
+    /// Flood fills each unlabelled plot's region with the next label.
+    fn label(&mut self, grid: &Grid) {
+        let mut stack = vec![];
+
+        for y in 0..grid.height {
+            for x in 0..grid.width {
+                if self.labels[(y * self.width + x) as usize] != usize::MAX {
+                    continue;
+                }
+
+                let label = self.regions.len();
+                let crop = grid.get(x, y);
+                self.regions.push(Region {
+                    crop,
+                    area: 0,
+                    perimeter: 0,
+                    sides: 0,
+                    bounds: BoundingBox {
+                        min_x: x as usize,
+                        min_y: y as usize,
+                        max_x: x as usize,
+                        max_y: y as usize,
+                    },
+                });
+
+                self.labels[(y * self.width + x) as usize] = label;
+                stack.push((x, y));
+                while let Some((x, y)) = stack.pop() {
+                    for (delta_x, delta_y) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                        let (x, y) = (x + delta_x, y + delta_y);
+                        if grid.contains(x, y)
+                            && grid.get(x, y) == crop
+                            && self.labels[(y * self.width + x) as usize] == usize::MAX
+                        {
+                            self.labels[(y * self.width + x) as usize] = label;
+                            stack.push((x, y));
+                        }
+                    }
+                }
             }
         }
     }
 
-    (area, perimeter)
-}
-
-fn trace_plot2(
-    crop: u8,
-    start: (i32, i32),
-    grid: &Grid,
-    visited: &mut HashSet<(i32, i32), BuildHasherDefault<FxHasher>>,
-) -> (u32, u32) {
-    let mut area = 0;
-    let mut sides = 0;
-    let mut queue = VecDeque::new();
-
-    queue.push_back(start);
-
-    while let Some(point) = queue.pop_front() {
-        if !visited.insert(point) {
-            continue;
+    /// Adds each plot's area, fences and corners to its region.
+    fn measure(&mut self) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let label = self.get(x, y);
+                let same = |delta_x: i32, delta_y: i32| self.get(x + delta_x, y + delta_y) == label;
+
+                let perimeter = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                    .into_iter()
+                    .filter(|&(delta_x, delta_y)| !same(delta_x, delta_y))
+                    .count() as u32;
+
+                // Each diagonal has an outer corner if both sides next to it are fenced, and an
+                // inner corner if neither is but the diagonal itself belongs to another region.
+                let corners = [(1, 1), (1, -1), (-1, 1), (-1, -1)]
+                    .into_iter()
+                    .filter(|&(delta_x, delta_y)| {
+                        let horizontal = same(delta_x, 0);
+                        let vertical = same(0, delta_y);
+                        (!horizontal && !vertical)
+                            || (horizontal && vertical && !same(delta_x, delta_y))
+                    })
+                    .count() as u32;
+
+                let region = &mut self.regions[label.unwrap()];
+                region.area += 1;
+                region.perimeter += perimeter;
+                region.sides += corners;
+                region.bounds.extend(x as usize, y as usize);
+            }
         }
+    }
 
-        let (x, y) = point;
-
-        area += 1;
-
-        let some_crop = Some(crop);
+    fn get(&self, x: i32, y: i32) -> Option<usize> {
+        (x >= 0 && x < self.width && y >= 0 && y < self.height)
+            .then(|| self.labels[(y * self.width + x) as usize])
+    }
 
-        // Outer corners
-        if grid.get_safe(x - 1, y) != some_crop && grid.get_safe(x, y - 1) != some_crop {
-            sides += 1;
-        }
-        if grid.get_safe(x - 1, y) != some_crop && grid.get_safe(x, y + 1) != some_crop {
-            sides += 1;
-        }
-        if grid.get_safe(x + 1, y) != some_crop && grid.get_safe(x, y - 1) != some_crop {
-            sides += 1;
-        }
-        if grid.get_safe(x + 1, y) != some_crop && grid.get_safe(x, y + 1) != some_crop {
-            sides += 1;
-        }
-        // Inner corners
-        if grid.get_safe(x - 1, y) == some_crop
-            && grid.get_safe(x, y - 1) == some_crop
-            && grid.get_safe(x - 1, y - 1) != some_crop
-        {
-            sides += 1;
+    /// The map with each plot replaced by its region's label, followed by the regions.
+    fn render(&self) -> String {
+        let digits = (self.regions.len() - 1).to_string().len();
+        let mut output = String::new();
+
+        for row in self.labels.chunks(self.width as usize) {
+            let row: Vec<String> = row
+                .iter()
+                .map(|label| format!("{label:>digits$}"))
+                .collect();
+            output.push_str(&row.join(" "));
+            output.push('\n');
         }
-        if grid.get_safe(x + 1, y) == some_crop
-            && grid.get_safe(x, y - 1) == some_crop
-            && grid.get_safe(x + 1, y - 1) != some_crop
-        {
-            sides += 1;
-        }
-        if grid.get_safe(x - 1, y) == some_crop
-            && grid.get_safe(x, y + 1) == some_crop
-            && grid.get_safe(x - 1, y + 1) != some_crop
-        {
-            sides += 1;
-        }
-        if grid.get_safe(x + 1, y) == some_crop
-            && grid.get_safe(x, y + 1) == some_crop
-            && grid.get_safe(x + 1, y + 1) != some_crop
-        {
-            sides += 1;
-        }
-
-        for (value, bordering_x, bordering_y) in grid.get_adjacent(x, y) {
-            if value == crop {
-                queue.push_back((bordering_x, bordering_y));
-            }
+        for (label, region) in self.regions.iter().enumerate() {
+            output.push_str(&format!("\n{label:>digits$} {region}"));
         }
+        output
     }
-
-    (area, sides)
 }
 
 pub fn part_one(input: &str) -> Option<u32> {
-    let grid = Grid::new(input);
-    let mut visited = FxHashSet::default();
-    let mut areas: Vec<u32> = vec![];
-    let mut perimeters: Vec<u32> = vec![];
-
-    for (y, row) in grid.grid.iter().enumerate() {
-        for (x, plot) in row.iter().enumerate() {
-            if visited.contains(&(x as i32, y as i32)) {
-                continue;
-            }
+    let regions = Regions::new(&Grid::new(input));
 
-            let (area, perimeter) = trace_plot(*plot, (x as i32, y as i32), &grid, &mut visited);
-            areas.push(area);
-            perimeters.push(perimeter);
-        }
+    if has_flag("--labels") {
+        println!("{}", regions.render());
     }
+
     Some(
-        areas
+        regions
+            .regions
             .iter()
-            .zip(perimeters)
-            .map(|(area, perimeter)| area * perimeter)
+            .map(|region| region.area * region.perimeter)
             .sum(),
     )
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
-    let grid = Grid::new(input);
-    let mut visited = FxHashSet::default();
-    let mut areas: Vec<u32> = vec![];
-    let mut perimeters: Vec<u32> = vec![];
-
-    for (y, row) in grid.grid.iter().enumerate() {
-        for (x, plot) in row.iter().enumerate() {
-            if visited.contains(&(x as i32, y as i32)) {
-                continue;
-            }
+    let regions = Regions::new(&Grid::new(input));
 
-            let (area, perimeter) = trace_plot2(*plot, (x as i32, y as i32), &grid, &mut visited);
-            areas.push(area);
-            perimeters.push(perimeter);
-        }
-    }
     Some(
-        areas
+        regions
+            .regions
             .iter()
-            .zip(perimeters)
-            .map(|(area, perimeter)| area * perimeter)
+            .map(|region| region.area * region.sides)
             .sum(),
     )
 }
@@ -242,4 +244,44 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(1206));
     }
+
+    #[test]
+    fn test_part_two_inner_corners() {
+        assert_eq!(part_two("EEEEE\nEXXXX\nEEEEE\nEXXXX\nEEEEE"), Some(236));
+        assert_eq!(
+            part_two("AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA"),
+            Some(368)
+        );
+    }
+
+    #[test]
+    fn test_regions() {
+        let regions = Regions::new(&Grid::new("AAAA\nBBCD\nBBCC\nEEEC"));
+
+        assert_eq!(regions.labels[..8], [0, 0, 0, 0, 1, 1, 2, 3]);
+        assert_eq!(
+            regions.regions[2],
+            Region {
+                crop: b'C',
+                area: 4,
+                perimeter: 10,
+                sides: 8,
+                bounds: BoundingBox {
+                    min_x: 2,
+                    min_y: 1,
+                    max_x: 3,
+                    max_y: 3,
+                },
+            }
+        );
+        assert_eq!(
+            regions.render(),
+            "0 0 0 0\n1 1 2 3\n1 1 2 2\n4 4 4 2\n\n\
+             0 A: area 4, perimeter 10, 4 sides, x 0..=3, y 0..=0\n\
+             1 B: area 4, perimeter 8, 4 sides, x 0..=1, y 1..=2\n\
+             2 C: area 4, perimeter 10, 8 sides, x 2..=3, y 1..=3\n\
+             3 D: area 1, perimeter 4, 4 sides, x 3..=3, y 1..=1\n\
+             4 E: area 3, perimeter 8, 4 sides, x 0..=2, y 3..=3"
+        );
+    }
 }
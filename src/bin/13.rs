@@ -1,52 +1,246 @@
-use itertools::Itertools;
+use std::fmt::Display;
+
+use advent_of_code::{parse::records, template::flag_value};
 
 advent_of_code::solution!(13);
 
-const RIGGED_BS: i64 = 10000000000000;
+/// Tokens it takes to press each button once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Costs {
+    a: i64,
+    b: i64,
+}
+
+const PUZZLE_COSTS: Costs = Costs { a: 3, b: 1 };
+/// What the unit conversion error adds to both prize coordinates in part two.
+const PRIZE_OFFSET: i64 = 10_000_000_000_000;
+
+#[derive(Debug)]
+struct Overflow;
 
-fn solve(x1: i64, x2: i64, y1: i64, y2: i64, z1: i64, z2: i64) -> i64 {
-    let a = (y2 * z1 - y1 * z2) / (y2 * x1 - x2 * y1);
-    let b = (z2 - x2 * a) / y2;
-    if (x1 * a + y1 * b, x2 * a + y2 * b) != (z1, z2) {
-        return 0;
+impl Display for Overflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "arithmetic overflow")
     }
-    a * 3 + b
 }
 
-fn parse(input: &str) -> Vec<(i64, i64, i64, i64, i64, i64)> {
-    let machines = input.split("\n\n");
-    machines
-        .map(|machine| {
-            machine
-                .split(|c: char| !c.is_ascii_digit())
-                .filter(|x| !x.is_empty())
-                .map(|x| x.parse().unwrap())
-                .collect_tuple()
-                .unwrap()
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Presses {
+    a: i64,
+    b: i64,
+    cost: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ClawMachine {
+    a: (i64, i64),
+    b: (i64, i64),
+    prize: (i64, i64),
+}
+
+impl ClawMachine {
+    fn with_offset(self, offset: i64) -> Result<Self, Overflow> {
+        let shift = |coordinate: i64| coordinate.checked_add(offset).ok_or(Overflow);
+        Ok(Self {
+            prize: (shift(self.prize.0)?, shift(self.prize.1)?),
+            ..self
+        })
+    }
+
+    /// The cheapest presses that reach the prize, or `None` if no presses do.
+    fn cheapest(&self, costs: Costs) -> Result<Option<Presses>, Overflow> {
+        let (ax, ay) = (self.a.0 as i128, self.a.1 as i128);
+        let (bx, by) = (self.b.0 as i128, self.b.1 as i128);
+        let (px, py) = (self.prize.0 as i128, self.prize.1 as i128);
+        let (cost_a, cost_b) = (costs.a as i128, costs.b as i128);
+
+        let determinant = ax * by - ay * bx;
+        let presses = if determinant != 0 {
+            // Cramer's rule: the only solution, if it is a whole number of presses
+            let a = px * by - py * bx;
+            let b = ax * py - ay * px;
+            (a % determinant == 0 && b % determinant == 0)
+                .then(|| (a / determinant, b / determinant))
+                .filter(|&(a, b)| a >= 0 && b >= 0)
+        } else {
+            // Both buttons move along one line, and so must the prize. Any axis the line is not
+            // perpendicular to then pins down a position on it.
+            if ax * py - ay * px != 0 || bx * py - by * px != 0 {
+                return Ok(None);
+            }
+            if ax != 0 || bx != 0 {
+                cheapest_on_line(ax, bx, px, cost_a, cost_b)?
+            } else if ay != 0 || by != 0 {
+                cheapest_on_line(ay, by, py, cost_a, cost_b)?
+            } else {
+                // neither button moves the claw
+                (px == 0 && py == 0).then_some((0, 0))
+            }
+        };
+
+        let Some((a, b)) = presses else {
+            return Ok(None);
+        };
+        let cost = a
+            .checked_mul(cost_a)
+            .and_then(|cost| cost.checked_add(b.checked_mul(cost_b)?))
+            .ok_or(Overflow)?;
+
+        Ok(Some(Presses {
+            a: a.try_into().map_err(|_| Overflow)?,
+            b: b.try_into().map_err(|_| Overflow)?,
+            cost: cost.try_into().map_err(|_| Overflow)?,
+        }))
+    }
+}
+
+/// Returns `(g, x, y)` with `a * x + b * y == g == gcd(a, b)`.
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        return if a < 0 { (-a, -1, 0) } else { (a, 1, 0) };
+    }
+    let (g, x, y) = extended_gcd(b, a % b);
+    (g, y, x - (a / b) * y)
+}
+
+fn div_floor(a: i128, b: i128) -> i128 {
+    let quotient = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        quotient - 1
+    } else {
+        quotient
+    }
+}
+
+fn div_ceil(a: i128, b: i128) -> i128 {
+    -div_floor(-a, b)
+}
+
+/// The cheapest non-negative `(a, b)` with `a * u + b * v == w`. The solutions lie on the line
+/// `(a0 + t * v / g, b0 - t * u / g)` and the cost is linear in `t`, so the cheapest is at
+/// whichever end of the valid range of `t` the cost falls towards.
+fn cheapest_on_line(
+    u: i128,
+    v: i128,
+    w: i128,
+    cost_a: i128,
+    cost_b: i128,
+) -> Result<Option<(i128, i128)>, Overflow> {
+    if u == 0 || v == 0 {
+        // only one button moves the claw, so the other stays unpressed
+        let (step, a_moves) = if u != 0 { (u, true) } else { (v, false) };
+        if w % step != 0 || w / step < 0 {
+            return Ok(None);
+        }
+        return Ok(Some(if a_moves {
+            (w / step, 0)
+        } else {
+            (0, w / step)
+        }));
+    }
+
+    let (g, x, y) = extended_gcd(u, v);
+    if w % g != 0 {
+        return Ok(None);
+    }
+    let (a0, b0) = (
+        x.checked_mul(w / g).ok_or(Overflow)?,
+        y.checked_mul(w / g).ok_or(Overflow)?,
+    );
+    let (step_a, step_b) = (v / g, -u / g);
+
+    // a0 + t * step_a >= 0 and b0 + t * step_b >= 0
+    let mut lowest = None;
+    let mut highest = None;
+    for (start, step) in [(a0, step_a), (b0, step_b)] {
+        if step > 0 {
+            let bound = div_ceil(-start, step);
+            lowest = Some(lowest.map_or(bound, |lowest: i128| lowest.max(bound)));
+        } else {
+            let bound = div_floor(-start, step);
+            highest = Some(highest.map_or(bound, |highest: i128| highest.min(bound)));
+        }
+    }
+    if let (Some(lowest), Some(highest)) = (lowest, highest) {
+        if lowest > highest {
+            return Ok(None);
+        }
+    }
+
+    let slope = cost_a
+        .checked_mul(step_a)
+        .and_then(|slope| slope.checked_add(cost_b.checked_mul(step_b)?))
+        .ok_or(Overflow)?;
+    let t = match slope {
+        0 => lowest.or(highest),
+        slope if slope > 0 => lowest,
+        _ => highest,
+    };
+    // no end to fall towards means the cost is unbounded below, so there is no cheapest
+    let Some(t) = t else {
+        return Ok(None);
+    };
+
+    let a = t.checked_mul(step_a).and_then(|step| a0.checked_add(step));
+    let b = t.checked_mul(step_b).and_then(|step| b0.checked_add(step));
+    Ok(Some((a.ok_or(Overflow)?, b.ok_or(Overflow)?)))
+}
+
+fn parse(input: &str) -> Vec<ClawMachine> {
+    records(input)
+        .map(|[ax, ay, bx, by, px, py]| ClawMachine {
+            a: (ax, ay),
+            b: (bx, by),
+            prize: (px, py),
         })
         .collect()
 }
 
+fn total_cost(machines: &[ClawMachine], costs: Costs) -> Result<i64, Overflow> {
+    let mut total: i64 = 0;
+    for machine in machines {
+        if let Some(presses) = machine.cheapest(costs)? {
+            total = total.checked_add(presses.cost).ok_or(Overflow)?;
+        }
+    }
+    Ok(total)
+}
+
+/// The button costs, overridden with `--costs <a>,<b>`.
+fn costs() -> Costs {
+    flag_value("--costs").map_or(PUZZLE_COSTS, |costs| {
+        let (a, b) = costs
+            .split_once(',')
+            .expect("--costs takes two comma-separated numbers");
+        Costs {
+            a: a.parse().expect("--costs takes numbers"),
+            b: b.parse().expect("--costs takes numbers"),
+        }
+    })
+}
+
 pub fn part_one(input: &str) -> Option<i64> {
     let machines = parse(input);
 
-    Some(
-        machines
-            .into_iter()
-            .map(|(x1, x2, y1, y2, z1, z2)| solve(x1, x2, y1, y2, z1, z2))
-            .sum(),
-    )
+    total_cost(&machines, costs())
+        .map_err(|error| eprintln!("Cannot total the tokens: {error}"))
+        .ok()
 }
 
 pub fn part_two(input: &str) -> Option<i64> {
-    let machines = parse(input);
+    let offset = flag_value("--offset").map_or(PRIZE_OFFSET, |offset| {
+        offset.parse().expect("--offset takes a number")
+    });
+    let machines: Vec<ClawMachine> = parse(input)
+        .into_iter()
+        .map(|machine| machine.with_offset(offset))
+        .collect::<Result<_, _>>()
+        .map_err(|error| eprintln!("Cannot move the prizes: {error}"))
+        .ok()?;
 
-    Some(
-        machines
-            .into_iter()
-            .map(|(x1, x2, y1, y2, z1, z2)| solve(x1, x2, y1, y2, z1 + RIGGED_BS, z2 + RIGGED_BS))
-            .sum(),
-    )
+    total_cost(&machines, costs())
+        .map_err(|error| eprintln!("Cannot total the tokens: {error}"))
+        .ok()
 }
 
 #[cfg(test)]
@@ -64,4 +258,111 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(875318608908));
     }
+
+    fn machine(a: (i64, i64), b: (i64, i64), prize: (i64, i64)) -> ClawMachine {
+        ClawMachine { a, b, prize }
+    }
+
+    fn cheapest(machine: ClawMachine, costs: Costs) -> Option<(i64, i64, i64)> {
+        machine
+            .cheapest(costs)
+            .unwrap()
+            .map(|presses| (presses.a, presses.b, presses.cost))
+    }
+
+    #[test]
+    fn test_collinear_buttons() {
+        let diagonal = machine((1, 1), (2, 2), (5, 5));
+        assert_eq!(cheapest(diagonal, PUZZLE_COSTS), Some((1, 2, 5)));
+        assert_eq!(cheapest(diagonal, Costs { a: 1, b: 3 }), Some((5, 0, 5)));
+
+        assert_eq!(
+            cheapest(machine((1, 1), (2, 2), (5, 6)), PUZZLE_COSTS),
+            None
+        );
+        assert_eq!(
+            cheapest(machine((2, 2), (4, 4), (5, 5)), PUZZLE_COSTS),
+            None
+        );
+        assert_eq!(
+            cheapest(machine((0, 1), (0, 3), (0, 7)), PUZZLE_COSTS),
+            Some((1, 2, 5))
+        );
+        assert_eq!(
+            cheapest(machine((0, 0), (0, 3), (0, 9)), PUZZLE_COSTS),
+            Some((0, 3, 3))
+        );
+        assert_eq!(
+            cheapest(machine((0, 0), (0, 0), (0, 0)), PUZZLE_COSTS),
+            Some((0, 0, 0))
+        );
+        assert_eq!(
+            cheapest(machine((0, 0), (0, 0), (1, 0)), PUZZLE_COSTS),
+            None
+        );
+    }
+
+    #[test]
+    fn test_large_collinear_prize() {
+        assert!(machine((1, 1), (1, 2), (1, 1))
+            .with_offset(i64::MAX)
+            .is_err());
+
+        let far = machine((3, 6), (5, 10), (PRIZE_OFFSET, 2 * PRIZE_OFFSET));
+        let (a, b, cost) = cheapest(far, PUZZLE_COSTS).unwrap();
+        assert_eq!((a * 3 + b * 5, a * 6 + b * 10), far.prize);
+        // B moves the claw further per token, so A is only pressed to make up the remainder
+        assert!(a < 5);
+        assert_eq!(cost, a * 3 + b);
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        let generate = |rng: &mut advent_of_code::random::Rng| {
+            let a = (1 + rng.below(6) as i64, 1 + rng.below(6) as i64);
+            // make half of the machines collinear
+            let b = if rng.below(2) == 0 {
+                let scale = 1 + rng.below(3) as i64;
+                (a.0 * scale, a.1 * scale)
+            } else {
+                (1 + rng.below(6) as i64, 1 + rng.below(6) as i64)
+            };
+            let (presses_a, presses_b) = (rng.below(20) as i64, rng.below(20) as i64);
+            let mut prize = (
+                presses_a * a.0 + presses_b * b.0,
+                presses_a * a.1 + presses_b * b.1,
+            );
+            if rng.below(4) == 0 {
+                prize.1 += 1;
+            }
+            let costs = Costs {
+                a: rng.below(5) as i64,
+                b: rng.below(5) as i64,
+            };
+            (machine(a, b, prize), costs)
+        };
+        let solve = |&(machine, costs): &(ClawMachine, Costs)| {
+            let (presses_a, presses_b, cost) = cheapest(machine, costs)?;
+            assert_eq!(
+                (
+                    presses_a * machine.a.0 + presses_b * machine.b.0,
+                    presses_a * machine.a.1 + presses_b * machine.b.1
+                ),
+                machine.prize
+            );
+            Some(cost)
+        };
+        let brute_force = |&(ClawMachine { a, b, prize }, costs): &(ClawMachine, Costs)| {
+            (0..=prize.0 / a.0)
+                .filter_map(|presses_a| {
+                    let rest = (prize.0 - presses_a * a.0, prize.1 - presses_a * a.1);
+                    let presses_b = rest.0 / b.0;
+                    (rest.0 % b.0 == 0 && rest == (presses_b * b.0, presses_b * b.1))
+                        .then_some(presses_a * costs.a + presses_b * costs.b)
+                })
+                .min()
+        };
+
+        advent_of_code::random::cross_check(13, 500, generate, solve, brute_force);
+    }
 }
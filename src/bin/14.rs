@@ -1,35 +1,46 @@
-use std::hash::Hash;
+use std::{fmt::Write, fs, hash::Hash};
 
-use rayon::prelude::*;
+use advent_of_code::template::flag_value;
 
 advent_of_code::solution!(14);
 
-#[cfg(test)]
-const WIDTH: i32 = 11;
-#[cfg(not(test))]
-const WIDTH: i32 = 101;
-#[cfg(test)]
-const HEIGHT: i32 = 7;
-#[cfg(not(test))]
-const HEIGHT: i32 = 103;
-
-#[cfg(test)]
-const STEPS: i32 = 100;
-#[cfg(not(test))]
 const STEPS: i32 = 100;
 
+/// The area the robots wrap around in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Room {
+    width: i32,
+    height: i32,
+}
+
+impl Room {
+    fn contains(&self, point: &Point) -> bool {
+        (0..self.width).contains(&point.x) && (0..self.height).contains(&point.y)
+    }
+}
+
+const PUZZLE_ROOM: Room = Room {
+    width: 101,
+    height: 103,
+};
+
+const EXAMPLE_ROOM: Room = Room {
+    width: 11,
+    height: 7,
+};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Point {
     x: i32,
     y: i32,
 }
 
-fn move_robot(robot: &mut Point, velocity: &Point) {
+fn move_robot(robot: &mut Point, velocity: &Point, room: Room) {
     let moved_x = robot.x + velocity.x;
     let moved_y = robot.y + velocity.y;
 
-    let normalized_x = moved_x.rem_euclid(WIDTH);
-    let normalized_y = moved_y.rem_euclid(HEIGHT);
+    let normalized_x = moved_x.rem_euclid(room.width);
+    let normalized_y = moved_y.rem_euclid(room.height);
 
     robot.x = normalized_x;
     robot.y = normalized_y;
@@ -61,22 +72,23 @@ fn parse(input: &str) -> Vec<(Point, Point)> {
         .collect()
 }
 
-fn safety(robots: &[(Point, Point)]) -> u32 {
+fn safety(robots: &[(Point, Point)], room: Room) -> u32 {
+    let (mid_x, mid_y) = (room.width / 2, room.height / 2);
     let mut upper_left = 0;
     let mut upper_right = 0;
     let mut lower_left = 0;
     let mut lower_right = 0;
     for (robot, _) in robots {
-        if robot.x < WIDTH / 2 && robot.y < HEIGHT / 2 {
+        if robot.x < mid_x && robot.y < mid_y {
             upper_left += 1;
         }
-        if robot.x > WIDTH / 2 && robot.y < HEIGHT / 2 {
+        if robot.x > mid_x && robot.y < mid_y {
             upper_right += 1;
         }
-        if robot.x < WIDTH / 2 && robot.y > HEIGHT / 2 {
+        if robot.x < mid_x && robot.y > mid_y {
             lower_left += 1;
         }
-        if robot.x > WIDTH / 2 && robot.y > HEIGHT / 2 {
+        if robot.x > mid_x && robot.y > mid_y {
             lower_right += 1;
         }
     }
@@ -84,77 +96,132 @@ fn safety(robots: &[(Point, Point)]) -> u32 {
     upper_left * upper_right * lower_left * lower_right
 }
 
-// bin the grid into arbitraily sized grids
-fn shannon_entropy(robots: &[(Point, Point)]) -> f64 {
-    let mut bins = [0.0; 4];
-    let mut num_bots = 0.0;
+fn safety_after(robots: &mut [(Point, Point)], room: Room, steps: i32) -> u32 {
+    for _ in 0..steps {
+        robots
+            .iter_mut()
+            .for_each(|(robot, velocity)| move_robot(robot, velocity, room));
+    }
 
-    for (robot, _) in robots {
-        if robot.x < WIDTH / 2 && robot.y < HEIGHT / 2 {
-            bins[0] += 1.0;
-            num_bots += 1.0;
-        }
-        if robot.x > WIDTH / 2 && robot.y < HEIGHT / 2 {
-            bins[1] += 1.0;
-            num_bots += 1.0;
-        }
-        if robot.x < WIDTH / 2 && robot.y > HEIGHT / 2 {
-            bins[2] += 1.0;
-            num_bots += 1.0;
-        }
-        if robot.x > WIDTH / 2 && robot.y > HEIGHT / 2 {
-            bins[3] += 1.0;
-            num_bots += 1.0;
+    safety(robots, room)
+}
+
+/// Where each robot is after `step` steps.
+fn positions_at(robots: &[(Point, Point)], room: Room, step: i64) -> Vec<Point> {
+    robots
+        .iter()
+        .map(|(robot, velocity)| Point {
+            x: (robot.x as i64 + velocity.x as i64 * step).rem_euclid(room.width as i64) as i32,
+            y: (robot.y as i64 + velocity.y as i64 * step).rem_euclid(room.height as i64) as i32,
+        })
+        .collect()
+}
+
+/// The step in `0..period` at which one coordinate of the robots is least spread out. The
+/// coordinate repeats with the period of its axis, so no other steps need checking.
+fn tightest_step(robots: &[(Point, Point)], period: i32, axis: impl Fn(&Point) -> i32) -> i32 {
+    let n = robots.len() as i64;
+    (0..period)
+        .min_by_key(|&step| {
+            let (sum, sum_of_squares) = robots.iter().fold((0, 0), |(sum, squares), robot| {
+                let value = (axis(&robot.0) as i64 + axis(&robot.1) as i64 * step as i64)
+                    .rem_euclid(period as i64);
+                (sum + value, squares + value * value)
+            });
+            // n² times the variance, which orders steps the same way
+            n * sum_of_squares - sum * sum
+        })
+        .unwrap()
+}
+
+/// Solves `t ≡ a (mod m)`, `t ≡ b (mod n)` for the smallest non-negative `t`, if `m` and `n` are
+/// coprime.
+fn crt(a: i64, m: i64, b: i64, n: i64) -> Option<i64> {
+    fn extended_gcd(a: i64, b: i64) -> (i64, i64, i64) {
+        if b == 0 {
+            (a, 1, 0)
+        } else {
+            let (gcd, x, y) = extended_gcd(b, a % b);
+            (gcd, y, x - (a / b) * y)
         }
     }
 
-    bins.iter()
-        .map(|x| {
-            let p = x / num_bots;
-            -p * (p + f64::EPSILON).log2()
-        })
-        .sum()
+    let (gcd, inverse, _) = extended_gcd(m, n);
+    if gcd != 1 {
+        return None;
+    }
+    let k = ((b - a) * inverse).rem_euclid(n);
+    Some((a + m * k).rem_euclid(m * n))
 }
 
-pub fn part_one(input: &str) -> Option<u32> {
-    let mut robots = parse(input);
+/// The first step at which the robots gather into a picture: the x positions are least spread
+/// out at the same step modulo the width as the picture, and likewise for y and the height.
+fn picture_step(robots: &[(Point, Point)], room: Room) -> Option<i64> {
+    let step_x = tightest_step(robots, room.width, |point| point.x);
+    let step_y = tightest_step(robots, room.height, |point| point.y);
 
-    for _ in 0..STEPS {
-        robots
-            .iter_mut()
-            .for_each(|(robot, velocity)| move_robot(robot, velocity));
+    crt(
+        step_x as i64,
+        room.width as i64,
+        step_y as i64,
+        room.height as i64,
+    )
+}
+
+/// Renders robot positions as a plain PGM image, brighter where more robots overlap.
+fn to_pgm(positions: &[Point], room: Room) -> String {
+    let mut counts = vec![0; (room.width * room.height) as usize];
+    for point in positions {
+        counts[(point.y * room.width + point.x) as usize] += 1;
+    }
+
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    let mut pgm = format!("P2\n{} {}\n{max}\n", room.width, room.height);
+    for row in counts.chunks(room.width as usize) {
+        let row: Vec<String> = row.iter().map(ToString::to_string).collect();
+        writeln!(pgm, "{}", row.join(" ")).unwrap();
+    }
+    pgm
+}
+
+/// The room given with `--room <width>x<height>`, or else the smaller of the example and puzzle
+/// rooms that every robot starts in.
+fn room(robots: &[(Point, Point)]) -> Room {
+    if let Some(size) = flag_value("--room") {
+        let (width, height) = size.split_once('x').expect("--room takes <width>x<height>");
+        return Room {
+            width: width.parse().expect("Invalid room width"),
+            height: height.parse().expect("Invalid room height"),
+        };
     }
 
-    Some(safety(&robots))
+    [EXAMPLE_ROOM, PUZZLE_ROOM]
+        .into_iter()
+        .find(|room| robots.iter().all(|(robot, _)| room.contains(robot)))
+        .unwrap_or(PUZZLE_ROOM)
 }
 
-pub fn part_two(input: &str) -> Option<usize> {
-    let step_num = 8200;
-    let mut safety_values: Vec<(usize, u32, f64)> = vec![(0, 0, 0.0); step_num];
+pub fn part_one(input: &str) -> Option<u32> {
     let mut robots = parse(input);
+    let room = room(&robots);
 
-    for (i, safety_val) in safety_values.iter_mut().enumerate().take(step_num) {
-        robots
-            .par_iter_mut()
-            .for_each(|(robot, velocity)| move_robot(robot, velocity));
-        *safety_val = (i, safety(&robots), shannon_entropy(&robots));
-    }
+    Some(safety_after(&mut robots, room, STEPS))
+}
 
-    let min_safety = safety_values
-        .par_iter()
-        .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap())
-        .expect("iteration is not empty");
+pub fn part_two(input: &str) -> Option<i64> {
+    let robots = parse(input);
+    let room = room(&robots);
 
-    let max_safety = safety_values
-        .par_iter()
-        .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap())
-        .expect("iteration is not empty");
+    let step = picture_step(&robots, room)?;
 
-    dbg!(min_safety);
-    dbg!(max_safety);
+    if let Some(path) = flag_value("--pgm") {
+        let frame = positions_at(&robots, room, step);
+        fs::write(&path, to_pgm(&frame, room)).expect("could not write PGM file");
+    }
 
-    Some(min_safety.0 + 1)
+    Some(step)
 }
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -165,9 +232,63 @@ mod tests {
         assert_eq!(result, Some(12));
     }
 
+    /// Robots that draw a filled triangle at step `target`, plus as many scattered robots.
+    fn picture_input(target: i64) -> String {
+        let mut rng = advent_of_code::random::Rng::new(14);
+        let mut robots = vec![];
+        for row in 0..20 {
+            for column in -row..=row {
+                robots.push((40 + column, 30 + row));
+            }
+        }
+        for _ in 0..robots.len() {
+            robots.push((rng.below(101) as i64, rng.below(103) as i64));
+        }
+
+        robots
+            .into_iter()
+            .map(|(x, y)| {
+                let (dx, dy) = (rng.below(201) as i64 - 100, rng.below(207) as i64 - 103);
+                // rewind from the picture to step 0
+                let start_x = (x - dx * target).rem_euclid(101);
+                let start_y = (y - dy * target).rem_euclid(103);
+                format!("p={start_x},{start_y} v={dx},{dy}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     #[test]
     fn test_part_two() {
-        let result = part_two(&advent_of_code::template::read_file("examples", DAY));
-        assert_eq!(result, Some(5));
+        let result = part_two(&picture_input(6287));
+        assert_eq!(result, Some(6287));
+    }
+
+    #[test]
+    fn test_room() {
+        let robots = parse(&advent_of_code::template::read_file("examples", DAY));
+        assert_eq!(room(&robots), EXAMPLE_ROOM);
+        assert_eq!(room(&parse("p=10,6 v=0,0\np=11,0 v=0,0")), PUZZLE_ROOM);
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt(2, 3, 3, 5), Some(8));
+        assert_eq!(crt(0, 11, 0, 7), Some(0));
+        assert_eq!(crt(1, 4, 3, 6), None);
+    }
+
+    #[test]
+    fn test_to_pgm() {
+        let room = Room {
+            width: 3,
+            height: 2,
+        };
+        let positions = [
+            Point { x: 0, y: 0 },
+            Point { x: 2, y: 1 },
+            Point { x: 2, y: 1 },
+        ];
+        assert_eq!(to_pgm(&positions, room), "P2\n3 2\n2\n1 0 0\n0 0 2\n");
     }
 }
@@ -1,9 +1,14 @@
 use std::{
     fmt::Display,
+    fs,
+    io::{self, BufRead, Write},
     mem,
     ops::{Add, AddAssign},
+    path::Path,
 };
 
+use advent_of_code::template::{flag_value, has_flag};
+
 advent_of_code::solution!(15);
 
 const DUMMY: Point = Point { x: 0, y: 0 };
@@ -57,7 +62,7 @@ impl AddAssign for Point {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum Direction {
     Up,
     Down,
@@ -76,19 +81,278 @@ impl Direction {
     }
 }
 
+impl Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::Up => write!(f, "^"),
+            Direction::Down => write!(f, "v"),
+            Direction::Left => write!(f, "<"),
+            Direction::Right => write!(f, ">"),
+        }
+    }
+}
+
+fn render(grid: &[Vec<Entity>]) -> String {
+    grid.iter()
+        .map(|row| row.iter().map(ToString::to_string).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Renders the grid as a plain PPM image, one pixel per tile.
+fn to_ppm(grid: &[Vec<Entity>]) -> String {
+    let mut ppm = format!("P3\n{} {}\n255\n", grid[0].len(), grid.len());
+    for row in grid {
+        let pixels: Vec<&str> = row
+            .iter()
+            .map(|entity| match entity {
+                Entity::Wall => "128 128 128",
+                Entity::Empty => "0 0 0",
+                Entity::Bot => "255 0 0",
+                Entity::Box | Entity::BoxLeft | Entity::BoxRight => "205 133 63",
+            })
+            .collect();
+        ppm.push_str(&pixels.join(" "));
+        ppm.push('\n');
+    }
+    ppm
+}
+
+/// The warehouse after move `number` of the instructions, counting from 1.
+#[derive(Debug, Clone)]
+struct Frame {
+    number: usize,
+    direction: Direction,
+    grid: Vec<Vec<Entity>>,
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Move {}:\n{}", self.direction, render(&self.grid))
+    }
+}
+
+/// Steps through a warehouse's instructions one move at a time, yielding a [`Frame`] per move.
+#[derive(Debug)]
+struct Replay {
+    initial: Warehouse,
+    warehouse: Warehouse,
+    instructions: Vec<Direction>,
+    next: usize,
+}
+
+impl Replay {
+    fn new(mut warehouse: Warehouse) -> Self {
+        let instructions = mem::take(&mut warehouse.instructions);
+        Self {
+            initial: warehouse.clone(),
+            warehouse,
+            instructions,
+            next: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// Number of moves made so far.
+    fn position(&self) -> usize {
+        self.next
+    }
+
+    /// Moves forwards or backwards to the state after move `number`, returning its frame, or
+    /// `None` for the initial state.
+    fn seek(&mut self, number: usize) -> Option<Frame> {
+        let number = number.min(self.len());
+        if number < self.next {
+            self.warehouse = self.initial.clone();
+            self.next = 0;
+        }
+        while self.next < number {
+            self.warehouse.step(self.instructions[self.next]);
+            self.next += 1;
+        }
+        self.frame()
+    }
+
+    /// The frame of the last move made, or `None` before the first.
+    fn frame(&self) -> Option<Frame> {
+        let &direction = self.instructions.get(self.next.checked_sub(1)?)?;
+        Some(Frame {
+            number: self.next,
+            direction,
+            grid: self.warehouse.grid.clone(),
+        })
+    }
+}
+
+impl Iterator for Replay {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &direction = self.instructions.get(self.next)?;
+        self.warehouse.step(direction);
+        self.next += 1;
+        self.frame()
+    }
+}
+
+/// Lets the user step through `replay` from the terminal.
+fn play(mut replay: Replay) {
+    const CLEAR: &str = "\x1b[2J\x1b[H";
+    let help = "[enter] next move, <n> jump to move n, r run to the end, q quit";
+
+    print!("{CLEAR}Initial state:\n{}\n{help}\n> ", replay.warehouse);
+    io::stdout().flush().unwrap();
+
+    for line in io::stdin().lock().lines() {
+        let target = match line.unwrap().trim() {
+            "" => replay.position() + 1,
+            "r" => replay.len(),
+            "q" => break,
+            number => match number.parse() {
+                Ok(number) => number,
+                Err(_) => {
+                    print!("{help}\n> ");
+                    io::stdout().flush().unwrap();
+                    continue;
+                }
+            },
+        };
+
+        match replay.seek(target) {
+            Some(frame) => println!("{CLEAR}{frame}"),
+            None => println!("{CLEAR}Initial state:\n{}", replay.warehouse),
+        }
+        print!("move {} of {}\n> ", replay.position(), replay.len());
+        io::stdout().flush().unwrap();
+    }
+}
+
+/// Writes the frames numbered in `numbers`, or all of them, to `directory` as text or PPM.
+fn export(replay: Replay, directory: &Path, part: u8, numbers: Option<&[usize]>, ppm: bool) {
+    fs::create_dir_all(directory).expect("could not create export directory");
+    let extension = if ppm { "ppm" } else { "txt" };
+
+    for frame in replay {
+        if numbers.is_some_and(|numbers| !numbers.contains(&frame.number)) {
+            continue;
+        }
+        let contents = if ppm {
+            to_ppm(&frame.grid)
+        } else {
+            format!("{frame}\n")
+        };
+        let path = directory.join(format!("part{part}-{:05}.{extension}", frame.number));
+        fs::write(path, contents).expect("could not write frame");
+    }
+}
+
+/// Runs the replay options: `--play`, and `--export <dir>` with `--frames <n,m,...>` and `--ppm`.
+fn inspect(warehouse: &Warehouse, part: u8) {
+    if has_flag("--play") {
+        println!("Part {part}");
+        play(Replay::new(warehouse.clone()));
+    }
+
+    if let Some(directory) = flag_value("--export") {
+        let numbers: Option<Vec<usize>> = flag_value("--frames").map(|numbers| {
+            numbers
+                .split(',')
+                .map(|number| number.trim().parse().expect("--frames takes move numbers"))
+                .collect()
+        });
+        export(
+            Replay::new(warehouse.clone()),
+            Path::new(&directory),
+            part,
+            numbers.as_deref(),
+            has_flag("--ppm"),
+        );
+    }
+}
+
+/// Tile counts that no move may change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Census {
+    walls: usize,
+    boxes: usize,
+    bots: usize,
+}
+
+impl Census {
+    fn of(grid: &[Vec<Entity>]) -> Self {
+        let mut census = Self {
+            walls: 0,
+            boxes: 0,
+            bots: 0,
+        };
+        for &cell in grid.iter().flatten() {
+            match cell {
+                Entity::Wall => census.walls += 1,
+                Entity::Box | Entity::BoxLeft => census.boxes += 1,
+                Entity::Bot => census.bots += 1,
+                Entity::Empty | Entity::BoxRight => (),
+            }
+        }
+        census
+    }
+}
+
+impl Display for Census {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} walls, {} boxes, {} bots",
+            self.walls, self.boxes, self.bots
+        )
+    }
+}
+
 #[derive(Debug)]
+enum InvariantError {
+    UnpairedHalf(Point),
+    WrongBoxSize(Point),
+    CensusChanged { expected: Census, found: Census },
+    BotMisplaced(Point),
+}
+
+impl Display for InvariantError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvariantError::UnpairedHalf(point) => {
+                write!(f, "box half at ({}, {}) has no partner", point.x, point.y)
+            }
+            InvariantError::WrongBoxSize(point) => write!(
+                f,
+                "box at ({}, {}) does not match the warehouse's box size",
+                point.x, point.y
+            ),
+            InvariantError::CensusChanged { expected, found } => {
+                write!(f, "expected {expected}, found {found}")
+            }
+            InvariantError::BotMisplaced(point) => {
+                write!(f, "bot is not at ({}, {})", point.x, point.y)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 struct Warehouse {
     grid: Vec<Vec<Entity>>,
     instructions: Vec<Direction>,
     bot: Point,
     is_doubled: bool,
+    census: Census,
 }
 
 impl Warehouse {
     fn new(input: &str) -> Self {
         let (grid, instructions) = input.trim_end().split_once("\n\n").unwrap();
         let mut bot = Point::default();
-        let grid = grid
+        let grid: Vec<Vec<Entity>> = grid
             .lines()
             .enumerate()
             .map(|(y, line)| {
@@ -124,6 +388,7 @@ impl Warehouse {
             .collect();
 
         Self {
+            census: Census::of(&grid),
             grid,
             instructions,
             bot,
@@ -176,6 +441,7 @@ impl Warehouse {
             .collect();
 
         Self {
+            census: Census::of(&grid),
             grid,
             instructions,
             bot,
@@ -309,23 +575,79 @@ impl Warehouse {
             .sum()
     }
 
-    fn process_instructions(&mut self) {
-        let move_func = if !self.is_doubled {
-            Self::move_small_boxes
+    /// Checks that boxes have the right size and are whole, that no wall, box or bot has been
+    /// created or destroyed, and that the bot is where we think it is.
+    fn check_invariants(&self) -> Result<(), InvariantError> {
+        for (y, row) in self.grid.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                let point = Point {
+                    x: x as i32,
+                    y: y as i32,
+                };
+                let paired = match cell {
+                    Entity::Box if self.is_doubled => {
+                        return Err(InvariantError::WrongBoxSize(point))
+                    }
+                    Entity::BoxLeft | Entity::BoxRight if !self.is_doubled => {
+                        return Err(InvariantError::WrongBoxSize(point))
+                    }
+                    Entity::BoxLeft => row.get(x + 1) == Some(&Entity::BoxRight),
+                    Entity::BoxRight => x > 0 && row[x - 1] == Entity::BoxLeft,
+                    _ => true,
+                };
+                if !paired {
+                    return Err(InvariantError::UnpairedHalf(point));
+                }
+            }
+        }
+
+        let found = Census::of(&self.grid);
+        if found != self.census {
+            return Err(InvariantError::CensusChanged {
+                expected: self.census,
+                found,
+            });
+        }
+
+        if self.get_tile(&self.bot) != Entity::Bot {
+            return Err(InvariantError::BotMisplaced(self.bot));
+        }
+        Ok(())
+    }
+
+    fn step(&mut self, direction: Direction) {
+        if self.is_doubled {
+            self.move_large_boxes(direction);
         } else {
-            Self::move_large_boxes
-        };
+            self.move_small_boxes(direction);
+        }
+
+        if cfg!(debug_assertions) {
+            if let Err(error) = self.check_invariants() {
+                panic!("Move {direction} broke the warehouse: {error}\n{self}");
+            }
+        }
+    }
+
+    fn process_instructions(&mut self) {
         // we don't need ownership of our whole self, just self.instructions
         // NOTE: self.instructions will be the default value after this
         let instructions = mem::take(&mut self.instructions);
         for instruction in instructions {
-            move_func(self, instruction);
+            self.step(instruction);
         }
     }
 }
 
+impl Display for Warehouse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", render(&self.grid))
+    }
+}
+
 pub fn part_one(input: &str) -> Option<u64> {
     let mut warehouse = Warehouse::new(input);
+    inspect(&warehouse, 1);
 
     warehouse.process_instructions();
     Some(warehouse.calculate_gps_sum())
@@ -333,6 +655,7 @@ pub fn part_one(input: &str) -> Option<u64> {
 
 pub fn part_two(input: &str) -> Option<u64> {
     let mut warehouse: Warehouse = Warehouse::big_new(input);
+    inspect(&warehouse, 2);
 
     warehouse.process_instructions();
     Some(warehouse.calculate_gps_sum())
@@ -341,6 +664,7 @@ pub fn part_two(input: &str) -> Option<u64> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
 
     #[test]
     fn test_part_one() {
@@ -353,4 +677,251 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(9021));
     }
+
+    const SMALL_EXAMPLE: &str = "\
+#######
+#...#.#
+#.....#
+#..OO@#
+#..O..#
+#.....#
+#######
+
+<vv<<^^<<^^";
+
+    #[test]
+    fn test_replay_worked_example() {
+        let warehouse = Warehouse::big_new(SMALL_EXAMPLE);
+        assert_eq!(
+            warehouse.to_string(),
+            "\
+##############
+##......##..##
+##..........##
+##....[][]@.##
+##....[]....##
+##..........##
+##############"
+        );
+
+        let frames: Vec<Frame> = Replay::new(warehouse).collect();
+        assert_eq!(frames.len(), 11);
+        assert_eq!(
+            frames[0].to_string(),
+            "\
+Move <:
+##############
+##......##..##
+##..........##
+##...[][]@..##
+##....[]....##
+##..........##
+##############"
+        );
+        assert_eq!(
+            render(&frames[10].grid),
+            "\
+##############
+##...[].##..##
+##...@.[]...##
+##....[]....##
+##..........##
+##..........##
+##############"
+        );
+    }
+
+    #[test]
+    fn test_replay_seek() {
+        let mut replay = Replay::new(Warehouse::big_new(SMALL_EXAMPLE));
+        let frames: Vec<Frame> = Replay::new(Warehouse::big_new(SMALL_EXAMPLE)).collect();
+
+        assert_eq!(replay.seek(7).unwrap().grid, frames[6].grid);
+        assert_eq!(replay.seek(3).unwrap().grid, frames[2].grid);
+        assert!(replay.seek(0).is_none());
+        assert_eq!(replay.position(), 0);
+        assert_eq!(replay.seek(100).unwrap().number, 11);
+
+        // seeking to where the replay already is shows that move again
+        assert_eq!(replay.seek(11).unwrap().grid, frames[10].grid);
+        replay.seek(4);
+        let frame = replay.seek(replay.position()).unwrap();
+        assert_eq!((frame.number, &frame.grid), (4, &frames[3].grid));
+    }
+
+    /// A straightforward model of the warehouse: boxes are sets of left edges `width` tiles wide,
+    /// and a push moves every box the bot's move touches, transitively, or nothing at all.
+    struct Reference {
+        walls: HashSet<(i32, i32)>,
+        boxes: HashSet<(i32, i32)>,
+        width: i32,
+        bot: (i32, i32),
+    }
+
+    impl Reference {
+        fn new(warehouse: &Warehouse) -> Self {
+            let mut reference = Self {
+                walls: HashSet::new(),
+                boxes: HashSet::new(),
+                width: if warehouse.is_doubled { 2 } else { 1 },
+                bot: (warehouse.bot.x, warehouse.bot.y),
+            };
+            for (y, row) in warehouse.grid.iter().enumerate() {
+                for (x, cell) in row.iter().enumerate() {
+                    let point = (x as i32, y as i32);
+                    match cell {
+                        Entity::Wall => _ = reference.walls.insert(point),
+                        Entity::Box | Entity::BoxLeft => _ = reference.boxes.insert(point),
+                        _ => (),
+                    }
+                }
+            }
+            reference
+        }
+
+        fn box_at(&self, (x, y): (i32, i32)) -> Option<(i32, i32)> {
+            (0..self.width)
+                .map(|offset| (x - offset, y))
+                .find(|left| self.boxes.contains(left))
+        }
+
+        fn step(&mut self, direction: Direction) {
+            let Point { x: dx, y: dy } = direction.vector();
+            let mut pushed = HashSet::new();
+            let mut frontier = vec![(self.bot.0 + dx, self.bot.1 + dy)];
+
+            while let Some(point) = frontier.pop() {
+                if self.walls.contains(&point) {
+                    return;
+                }
+                if let Some(left) = self.box_at(point) {
+                    if pushed.insert(left) {
+                        frontier.extend(
+                            (0..self.width).map(|offset| (left.0 + offset + dx, left.1 + dy)),
+                        );
+                    }
+                }
+            }
+
+            for left in &pushed {
+                self.boxes.remove(left);
+            }
+            self.boxes
+                .extend(pushed.iter().map(|&(x, y)| (x + dx, y + dy)));
+            self.bot = (self.bot.0 + dx, self.bot.1 + dy);
+        }
+
+        fn grid(&self, width: usize, height: usize) -> Vec<Vec<Entity>> {
+            let mut grid = vec![vec![Entity::Empty; width]; height];
+            let mut set = |(x, y): (i32, i32), entity| grid[y as usize][x as usize] = entity;
+            for &wall in &self.walls {
+                set(wall, Entity::Wall);
+            }
+            for &(x, y) in &self.boxes {
+                if self.width == 1 {
+                    set((x, y), Entity::Box);
+                } else {
+                    set((x, y), Entity::BoxLeft);
+                    set((x + 1, y), Entity::BoxRight);
+                }
+            }
+            set(self.bot, Entity::Bot);
+            grid
+        }
+    }
+
+    /// A walled-in warehouse in puzzle input format with random walls, boxes and moves.
+    fn random_warehouse(rng: &mut advent_of_code::random::Rng) -> String {
+        let size = 3 + rng.below(8) as usize;
+        let mut cells = vec![vec!['#'; size]; size];
+        for row in &mut cells[1..size - 1] {
+            for cell in &mut row[1..size - 1] {
+                *cell = match rng.below(10) {
+                    0 => '#',
+                    1..=4 => 'O',
+                    _ => '.',
+                };
+            }
+        }
+        let x = 1 + rng.below(size as u64 - 2) as usize;
+        let y = 1 + rng.below(size as u64 - 2) as usize;
+        cells[y][x] = '@';
+
+        let grid = cells
+            .iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let moves: String = (0..200)
+            .map(|_| ['^', 'v', '<', '>'][rng.below(4) as usize])
+            .collect();
+        format!("{grid}\n\n{moves}")
+    }
+
+    /// The grid after every move, in both the narrow and the doubled warehouse.
+    fn replay_grids(input: &str) -> Vec<Vec<Vec<Entity>>> {
+        [Warehouse::new(input), Warehouse::big_new(input)]
+            .into_iter()
+            .flat_map(|warehouse| Replay::new(warehouse).map(|frame| frame.grid))
+            .collect()
+    }
+
+    fn reference_grids(input: &str) -> Vec<Vec<Vec<Entity>>> {
+        let mut grids = vec![];
+        for mut warehouse in [Warehouse::new(input), Warehouse::big_new(input)] {
+            let mut reference = Reference::new(&warehouse);
+            let (width, height) = (warehouse.grid[0].len(), warehouse.grid.len());
+            for direction in mem::take(&mut warehouse.instructions) {
+                reference.step(direction);
+                grids.push(reference.grid(width, height));
+            }
+        }
+        grids
+    }
+
+    #[test]
+    fn test_against_reference() {
+        advent_of_code::random::cross_check(
+            15,
+            200,
+            random_warehouse,
+            |input| replay_grids(input),
+            |input| reference_grids(input),
+        );
+    }
+
+    #[test]
+    fn test_invariant_violations() {
+        let mut warehouse = Warehouse::big_new(SMALL_EXAMPLE);
+        assert!(warehouse.check_invariants().is_ok());
+
+        warehouse.grid[3][6] = Entity::Empty;
+        assert!(matches!(
+            warehouse.check_invariants(),
+            Err(InvariantError::UnpairedHalf(Point { x: 7, y: 3 }))
+        ));
+
+        let mut warehouse = Warehouse::big_new(SMALL_EXAMPLE);
+        warehouse.grid[0][0] = Entity::Empty;
+        assert!(matches!(
+            warehouse.check_invariants(),
+            Err(InvariantError::CensusChanged { .. })
+        ));
+
+        let mut warehouse = Warehouse::new(SMALL_EXAMPLE);
+        warehouse.bot += RIGHT;
+        assert!(matches!(
+            warehouse.check_invariants(),
+            Err(InvariantError::BotMisplaced(_))
+        ));
+    }
+
+    #[test]
+    fn test_to_ppm() {
+        let grid = vec![vec![Entity::Wall, Entity::Bot, Entity::BoxLeft]];
+        assert_eq!(
+            to_ppm(&grid),
+            "P3\n3 1\n255\n128 128 128 255 0 0 205 133 63\n"
+        );
+    }
 }
@@ -1,7 +1,10 @@
-use std::ops::{Add, AddAssign, Sub};
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    ops::{Add, AddAssign, Sub},
+};
 
-use fxhash::FxHashSet;
-use pathfinding::prelude::{astar, astar_bag};
+use advent_of_code::template::{flag_value, has_flag};
 
 advent_of_code::solution!(16);
 
@@ -48,6 +51,17 @@ enum Direction {
 }
 
 impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
     fn vector(&self) -> Point {
         match self {
             Direction::Up => Point { x: 0, y: -1 },
@@ -57,6 +71,15 @@ impl Direction {
         }
     }
 
+    fn arrow(self) -> u8 {
+        match self {
+            Direction::Up => b'^',
+            Direction::Down => b'v',
+            Direction::Left => b'<',
+            Direction::Right => b'>',
+        }
+    }
+
     fn rotate_clockwise(self) -> Self {
         match self {
             Direction::Up => Direction::Right,
@@ -82,10 +105,6 @@ struct Reindeer {
     direction: Direction,
 }
 
-fn taxicab_distance(point0: Point, point1: Point) -> u32 {
-    point0.x.abs_diff(point1.x) + point0.y.abs_diff(point1.y)
-}
-
 fn get(grid: &[Vec<u8>], point: &Point) -> u8 {
     grid[point.y as usize][point.x as usize]
 }
@@ -146,6 +165,34 @@ fn successors(reindeer: &Reindeer, grid: &[Vec<u8>]) -> Vec<(Reindeer, u32)> {
     positions
 }
 
+/// The states that reach `reindeer` in one move of [`successors`], with the cost of that move.
+fn predecessors(reindeer: &Reindeer, grid: &[Vec<u8>]) -> Vec<(Reindeer, u32)> {
+    let previous = reindeer.position - reindeer.direction.vector();
+    if get(grid, &previous) == b'#' {
+        return vec![];
+    }
+
+    Direction::ALL
+        .into_iter()
+        .map(|direction| {
+            let cost = if direction == reindeer.direction {
+                1
+            } else if direction.rotate_clockwise().rotate_clockwise() == reindeer.direction {
+                2001
+            } else {
+                1001
+            };
+            (
+                Reindeer {
+                    position: previous,
+                    direction,
+                },
+                cost,
+            )
+        })
+        .collect()
+}
+
 fn parse(input: &str) -> (Vec<Vec<u8>>, Reindeer, Point) {
     let grid: Vec<Vec<u8>> = input
         .trim_end()
@@ -169,33 +216,253 @@ fn parse(input: &str) -> (Vec<Vec<u8>>, Reindeer, Point) {
     (grid, reindeer, end)
 }
 
+fn state_index(grid: &[Vec<u8>], reindeer: &Reindeer) -> usize {
+    let cell = reindeer.position.y as usize * grid[0].len() + reindeer.position.x as usize;
+    cell * 4 + reindeer.direction.index()
+}
+
+/// Cheapest cost from any of `sources` to every state, indexed by [`state_index`], following
+/// the moves `next` offers. The search stops once it has passed the cost of the first state that
+/// is a `target`; states beyond that, or unreachable, cost `u32::MAX`.
+fn dijkstra(
+    grid: &[Vec<u8>],
+    sources: impl IntoIterator<Item = Reindeer>,
+    next: impl Fn(&Reindeer, &[Vec<u8>]) -> Vec<(Reindeer, u32)>,
+    target: impl Fn(&Reindeer) -> bool,
+) -> Vec<u32> {
+    let mut costs = vec![u32::MAX; grid.len() * grid[0].len() * 4];
+    let mut queue = BinaryHeap::new();
+    for source in sources {
+        costs[state_index(grid, &source)] = 0;
+        queue.push(Reverse((
+            0,
+            source.position.y,
+            source.position.x,
+            source.direction.index(),
+        )));
+    }
+
+    let mut limit = u32::MAX;
+    while let Some(Reverse((cost, y, x, direction))) = queue.pop() {
+        if cost > limit {
+            break;
+        }
+        let reindeer = Reindeer {
+            position: Point { x, y },
+            direction: Direction::ALL[direction],
+        };
+        if cost > costs[state_index(grid, &reindeer)] {
+            continue;
+        }
+        if target(&reindeer) {
+            limit = limit.min(cost);
+        }
+
+        for (neighbour, step) in next(&reindeer, grid) {
+            let index = state_index(grid, &neighbour);
+            if cost + step < costs[index] {
+                costs[index] = cost + step;
+                queue.push(Reverse((
+                    cost + step,
+                    neighbour.position.y,
+                    neighbour.position.x,
+                    neighbour.direction.index(),
+                )));
+            }
+        }
+    }
+
+    costs
+}
+
+/// The cheapest way into `end` facing any direction.
+fn cost_to(grid: &[Vec<u8>], costs: &[u32], end: &Point) -> u32 {
+    Direction::ALL
+        .into_iter()
+        .map(|direction| {
+            costs[state_index(
+                grid,
+                &Reindeer {
+                    position: *end,
+                    direction,
+                },
+            )]
+        })
+        .min()
+        .unwrap()
+}
+
 fn find_path(grid: &[Vec<u8>], reindeer: &Reindeer, end: &Point) -> u32 {
-    let (_, cost) = astar(
-        reindeer,
-        |reindeer| successors(reindeer, grid),
-        |reindeer| taxicab_distance(reindeer.position, *end),
-        |reindeer| reindeer.position == *end,
-    )
-    .unwrap();
+    let forward = dijkstra(grid, [*reindeer], successors, |state| {
+        state.position == *end
+    });
+
+    cost_to(grid, &forward, end)
+}
 
-    cost
+/// Costs from the start and to the end for every state. A state lies on an optimal route exactly
+/// when its two costs add up to the best total.
+struct BestPaths {
+    forward: Vec<u32>,
+    backward: Vec<u32>,
+    best: u32,
+}
+
+impl BestPaths {
+    fn new(grid: &[Vec<u8>], reindeer: &Reindeer, end: &Point) -> Self {
+        let forward = dijkstra(grid, [*reindeer], successors, |state| {
+            state.position == *end
+        });
+        let ends = Direction::ALL.map(|direction| Reindeer {
+            position: *end,
+            direction,
+        });
+        let backward = dijkstra(grid, ends, predecessors, |state| state == reindeer);
+        let best = cost_to(grid, &forward, end);
+
+        Self {
+            forward,
+            backward,
+            best,
+        }
+    }
+
+    fn on_optimal_route(&self, state: usize) -> bool {
+        self.is_optimal_move(state, 0, state)
+    }
+
+    /// Every tile that some optimal route passes through, facing any direction.
+    fn tiles<'a>(&'a self, grid: &'a [Vec<u8>]) -> impl Iterator<Item = Point> + 'a {
+        (0..grid.len() as i32)
+            .flat_map(|y| (0..grid[0].len() as i32).map(move |x| Point { x, y }))
+            .filter(|&position| {
+                Direction::ALL.into_iter().any(|direction| {
+                    self.on_optimal_route(state_index(
+                        grid,
+                        &Reindeer {
+                            position,
+                            direction,
+                        },
+                    ))
+                })
+            })
+    }
+
+    /// Whether moving from state `from` to state `to` at `cost` keeps a route optimal.
+    fn is_optimal_move(&self, from: usize, cost: u32, to: usize) -> bool {
+        self.forward[from]
+            .checked_add(cost)
+            .and_then(|cost| cost.checked_add(self.backward[to]))
+            == Some(self.best)
+    }
+}
+
+/// Up to `limit` of the optimal routes, each with its cost, found by following only optimal
+/// moves from the start.
+fn best_routes(
+    grid: &[Vec<u8>],
+    reindeer: &Reindeer,
+    end: &Point,
+    limit: usize,
+) -> Vec<(Vec<Reindeer>, u32)> {
+    fn extend(
+        grid: &[Vec<u8>],
+        paths: &BestPaths,
+        end: &Point,
+        limit: usize,
+        route: &mut Vec<Reindeer>,
+        routes: &mut Vec<(Vec<Reindeer>, u32)>,
+    ) {
+        let current = *route.last().unwrap();
+        if current.position == *end {
+            routes.push((route.clone(), paths.best));
+            return;
+        }
+
+        let from = state_index(grid, &current);
+        for (next, cost) in successors(&current, grid) {
+            if routes.len() >= limit {
+                return;
+            }
+            if paths.is_optimal_move(from, cost, state_index(grid, &next)) {
+                route.push(next);
+                extend(grid, paths, end, limit, route, routes);
+                route.pop();
+            }
+        }
+    }
+
+    let paths = BestPaths::new(grid, reindeer, end);
+    let mut routes = vec![];
+    extend(grid, &paths, end, limit, &mut vec![*reindeer], &mut routes);
+    routes
 }
 
 fn find_all_path(grid: &[Vec<u8>], reindeer: &Reindeer, end: &Point) -> u32 {
-    let (paths, _) = astar_bag(
-        reindeer,
-        |reindeer| successors(reindeer, grid),
-        |reindeer| taxicab_distance(reindeer.position, *end),
-        |reindeer| reindeer.position == *end,
-    )
-    .unwrap();
-
-    let points: FxHashSet<Point> = paths.fold(FxHashSet::default(), |mut set, reindeer_path| {
-        set.extend(reindeer_path.iter().map(|reindeer| reindeer.position));
-        set
-    });
+    let paths = BestPaths::new(grid, reindeer, end);
+
+    paths.tiles(grid).count() as u32
+}
+
+/// Re-adds the cost of every move along `route` as `successors` prices it.
+fn route_cost(grid: &[Vec<u8>], route: &[Reindeer]) -> u32 {
+    route
+        .windows(2)
+        .map(|pair| {
+            successors(&pair[0], grid)
+                .into_iter()
+                .find(|(next, _)| *next == pair[1])
+                .map(|(_, cost)| cost)
+                .expect("consecutive states are a single move apart")
+        })
+        .sum()
+}
+
+/// The moves of `route` as runs of arrows, e.g. `^3 >1 ^2` for three steps up, one right and
+/// two up.
+fn describe_route(route: &[Reindeer]) -> String {
+    let mut runs: Vec<(Direction, usize)> = vec![];
+    for reindeer in &route[1..] {
+        match runs.last_mut() {
+            Some((direction, count)) if *direction == reindeer.direction => *count += 1,
+            _ => runs.push((reindeer.direction, 1)),
+        }
+    }
+
+    runs.iter()
+        .map(|(direction, count)| format!("{}{count}", direction.arrow() as char))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
 
-    points.len() as u32
+/// Draws the maze with every tile on an optimal route as `O`, and the tiles where an optimal route
+/// turns as the arrow of its new heading.
+fn render(grid: &[Vec<u8>], paths: &BestPaths) -> String {
+    let mut drawing = grid.to_vec();
+    for tile in paths.tiles(grid) {
+        drawing[tile.y as usize][tile.x as usize] = b'O';
+
+        for direction in Direction::ALL {
+            let reindeer = Reindeer {
+                position: tile,
+                direction,
+            };
+            let from = state_index(grid, &reindeer);
+            for (next, cost) in successors(&reindeer, grid) {
+                if next.direction != direction
+                    && paths.is_optimal_move(from, cost, state_index(grid, &next))
+                {
+                    drawing[tile.y as usize][tile.x as usize] = next.direction.arrow();
+                }
+            }
+        }
+    }
+
+    drawing
+        .into_iter()
+        .map(|row| String::from_utf8(row).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 pub fn part_one(input: &str) -> Option<u32> {
@@ -205,6 +472,25 @@ pub fn part_one(input: &str) -> Option<u32> {
 
 pub fn part_two(input: &str) -> Option<u32> {
     let (grid, reindeer, end) = parse(input);
+
+    if has_flag("--draw") {
+        let paths = BestPaths::new(&grid, &reindeer, &end);
+        println!("{}", render(&grid, &paths));
+    }
+    if has_flag("--routes") {
+        // there can be exponentially many optimal routes, so only the listing is capped
+        let limit = flag_value("--routes")
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(100);
+        for (route, cost) in best_routes(&grid, &reindeer, &end, limit) {
+            println!(
+                "cost {cost} (re-added {}): {}",
+                route_cost(&grid, &route),
+                describe_route(&route)
+            );
+        }
+    }
+
     Some(find_all_path(&grid, &reindeer, &end))
 }
 
@@ -223,4 +509,93 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(64));
     }
+
+    #[test]
+    fn test_render_matches_puzzle() {
+        let (grid, reindeer, end) = parse(&advent_of_code::template::read_file("examples", DAY));
+        let drawing = render(&grid, &BestPaths::new(&grid, &reindeer, &end));
+
+        // the puzzle draws turns as plain `O`
+        let plain: String = drawing
+            .chars()
+            .map(|c| if "^v<>".contains(c) { 'O' } else { c })
+            .collect();
+        assert_eq!(
+            plain,
+            "\
+#################
+#...#...#...#..O#
+#.#.#.#.#.#.#.#O#
+#.#.#.#...#...#O#
+#.#.#.#.###.#.#O#
+#OOO#.#.#.....#O#
+#O#O#.#.#.#####O#
+#O#O..#.#.#OOOOO#
+#O#O#####.#O###O#
+#O#O#..OOOOO#OOO#
+#O#O###O#####O###
+#O#O#OOO#..OOO#.#
+#O#O#O#####O###.#
+#O#O#OOOOOOO..#.#
+#O#O#O#########.#
+#O#OOO..........#
+#################"
+        );
+        // the start, where every route turns north
+        assert_eq!(drawing.lines().nth(15).unwrap().as_bytes()[1], b'^');
+    }
+
+    #[test]
+    fn test_matches_astar_bag() {
+        let generate = |rng: &mut advent_of_code::random::Rng| {
+            let size = 13;
+            (0..size)
+                .map(|y| {
+                    (0..size)
+                        .map(|x| {
+                            let border = x == 0 || y == 0 || x == size - 1 || y == size - 1;
+                            match (x, y) {
+                                (1, 11) => 'S',
+                                (11, 1) => 'E',
+                                _ if border || rng.below(4) == 0 => '#',
+                                _ => '.',
+                            }
+                        })
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let solve = |input: &String| {
+            let (grid, reindeer, end) = parse(input);
+            let cost = find_path(&grid, &reindeer, &end);
+            (cost != u32::MAX).then(|| (cost, find_all_path(&grid, &reindeer, &end)))
+        };
+        let astar_bag = |input: &String| {
+            let (grid, reindeer, end) = parse(input);
+            let (paths, cost) = pathfinding::prelude::astar_bag(
+                &reindeer,
+                |reindeer| successors(reindeer, &grid),
+                |_| 0,
+                |reindeer| reindeer.position == end,
+            )?;
+            let tiles: std::collections::HashSet<Point> =
+                paths.flatten().map(|reindeer| reindeer.position).collect();
+            Some((cost, tiles.len() as u32))
+        };
+
+        advent_of_code::random::cross_check(16, 30, generate, solve, astar_bag);
+    }
+
+    #[test]
+    fn test_routes_cost() {
+        let (grid, reindeer, end) = parse(&advent_of_code::template::read_file("examples", DAY));
+        let routes = best_routes(&grid, &reindeer, &end, usize::MAX);
+        assert!(routes.len() > 1);
+        for (route, cost) in &routes {
+            assert_eq!(*cost, 11048);
+            assert_eq!(route_cost(&grid, route), 11048);
+        }
+        assert!(describe_route(&routes[0].0).starts_with("^"));
+    }
 }
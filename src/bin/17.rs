@@ -1,4 +1,7 @@
 #![allow(clippy::upper_case_acronyms)]
+use std::fmt::Display;
+
+use advent_of_code::template::has_flag;
 use fxhash::FxHashSet;
 use itertools::Itertools;
 
@@ -15,7 +18,7 @@ impl From<Opcode> for OperandType {
             Opcode::ADV => Self::Combo,
             Opcode::BXL => Self::Literal,
             Opcode::BST => Self::Combo,
-            Opcode::JNZ => Self::Combo,
+            Opcode::JNZ => Self::Literal,
             Opcode::BXC => Self::Literal,
             Opcode::OUT => Self::Combo,
             Opcode::BDV => Self::Combo,
@@ -24,7 +27,7 @@ impl From<Opcode> for OperandType {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Opcode {
     ADV,
     BXL,
@@ -52,15 +55,200 @@ impl From<usize> for Opcode {
     }
 }
 
+/// How a combo operand reads in assembly.
+fn combo_name(operand: usize) -> String {
+    match operand {
+        0..=3 => operand.to_string(),
+        4 => "a".into(),
+        5 => "b".into(),
+        6 => "c".into(),
+        _ => unreachable!(),
+    }
+}
+
+impl Opcode {
+    fn assembly(self, operand: usize) -> String {
+        match self {
+            Opcode::ADV => format!("a >>= {}", combo_name(operand)),
+            Opcode::BXL => format!("b ^= {operand}"),
+            Opcode::BST => format!("b = {} % 8", combo_name(operand)),
+            Opcode::JNZ => format!("jnz {operand}"),
+            Opcode::BXC => "b ^= c".into(),
+            Opcode::OUT => format!("out {} % 8", combo_name(operand)),
+            Opcode::BDV => format!("b = a >> {}", combo_name(operand)),
+            Opcode::CDV => format!("c = a >> {}", combo_name(operand)),
+        }
+    }
+}
+
+/// A register value in terms of the registers at the start of a loop iteration.
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Register(char),
+    Literal(usize),
+    Xor(Vec<Expr>),
+    Mod8(Box<Expr>),
+    Shr(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn xor(self, rhs: Expr) -> Expr {
+        let mut terms = vec![];
+        let mut literal = 0;
+        for term in [self, rhs] {
+            match term {
+                Expr::Xor(inner) => terms.extend(inner),
+                term => terms.push(term),
+            }
+        }
+        terms.retain(|term| match term {
+            Expr::Literal(value) => {
+                literal ^= value;
+                false
+            }
+            _ => true,
+        });
+        if literal != 0 || terms.is_empty() {
+            terms.push(Expr::Literal(literal));
+        }
+
+        if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            Expr::Xor(terms)
+        }
+    }
+
+    fn mod8(self) -> Expr {
+        match self {
+            Expr::Literal(value) => Expr::Literal(value % 8),
+            Expr::Mod8(_) => self,
+            _ => Expr::Mod8(Box::new(self)),
+        }
+    }
+
+    fn shr(self, rhs: Expr) -> Expr {
+        match rhs {
+            Expr::Literal(0) => self,
+            _ => Expr::Shr(Box::new(self), Box::new(rhs)),
+        }
+    }
+
+    #[cfg(test)]
+    fn eval(&self, a: usize, b: usize, c: usize) -> usize {
+        match self {
+            Expr::Register('a') => a,
+            Expr::Register('b') => b,
+            Expr::Register(_) => c,
+            Expr::Literal(value) => *value,
+            Expr::Xor(terms) => terms.iter().fold(0, |acc, term| acc ^ term.eval(a, b, c)),
+            Expr::Mod8(inner) => inner.eval(a, b, c) % 8,
+            Expr::Shr(lhs, rhs) => lhs
+                .eval(a, b, c)
+                .checked_shr(rhs.eval(a, b, c) as u32)
+                .unwrap_or(0),
+        }
+    }
+
+    fn is_compound(&self) -> bool {
+        !matches!(self, Expr::Register(_) | Expr::Literal(_))
+    }
+
+    fn fmt_operand(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_compound() {
+            write!(f, "({self})")
+        } else {
+            write!(f, "{self}")
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Register(name) => write!(f, "{name}"),
+            Expr::Literal(value) => write!(f, "{value}"),
+            Expr::Xor(terms) => {
+                for (index, term) in terms.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " ^ ")?;
+                    }
+                    term.fmt_operand(f)?;
+                }
+                Ok(())
+            }
+            Expr::Mod8(inner) => {
+                inner.fmt_operand(f)?;
+                write!(f, " % 8")
+            }
+            Expr::Shr(lhs, rhs) => {
+                lhs.fmt_operand(f)?;
+                write!(f, " >> ")?;
+                rhs.fmt_operand(f)
+            }
+        }
+    }
+}
+
+/// Closed form of one loop iteration: the value it outputs and the value `a` continues with.
+#[derive(Debug)]
+struct LoopBody {
+    output: Expr,
+    next_a: Expr,
+}
+
+impl Display for LoopBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "out = {}", self.output)?;
+        write!(f, "a = {}", self.next_a)
+    }
+}
+
+/// Why a program can't be searched for a quine one output digit at a time.
+#[derive(Debug, PartialEq)]
+enum QuineError {
+    /// The program doesn't end with the only `jnz 0`, so it isn't a single loop.
+    NotSingleLoop,
+    /// The loop must shift `a` right by exactly 3 bits, once, through `adv 3`.
+    ShiftNotThree,
+    /// The loop must output exactly one value per iteration.
+    OutputCount(usize),
+    /// Every candidate for some output digit was rejected.
+    NoQuine,
+}
+
+impl Display for QuineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuineError::NotSingleLoop => {
+                write!(f, "program is not a single loop ending in `jnz 0`")
+            }
+            QuineError::ShiftNotThree => {
+                write!(
+                    f,
+                    "program must shift `a` by 3 bits exactly once with `adv 3`"
+                )
+            }
+            QuineError::OutputCount(count) => {
+                write!(f, "program must output once per loop, found {count} `out`")
+            }
+            QuineError::NoQuine => write!(f, "no value of `a` makes the program output itself"),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Computer {
     a: usize,
     b: usize,
     c: usize,
+    initial_b: usize,
+    initial_c: usize,
     counter: usize,
     program: Vec<usize>,
     stopped: bool,
     output: Vec<usize>,
+    trace: bool,
 }
 
 impl Computer {
@@ -74,14 +262,20 @@ impl Computer {
         let (_, program) = program.split_once(": ").unwrap();
         let program = program.split(",").flat_map(str::parse).collect();
 
+        let b = b.parse().unwrap();
+        let c = c.parse().unwrap();
+
         Self {
             a: a.parse().unwrap(),
-            b: b.parse().unwrap(),
-            c: c.parse().unwrap(),
+            b,
+            c,
+            initial_b: b,
+            initial_c: c,
             counter: 0,
             program,
             stopped: false,
             output: Vec::new(),
+            trace: false,
         }
     }
 
@@ -102,8 +296,19 @@ impl Computer {
                 },
             };
 
+            let address = self.counter;
             self.execute_opcode(opcode, operand);
             self.counter = self.counter.wrapping_add(2);
+
+            if self.trace {
+                eprintln!(
+                    "{address:02}: {:<12} a={:#o} b={} c={}",
+                    opcode.assembly(self.program[address + 1]),
+                    self.a,
+                    self.b,
+                    self.c
+                );
+            }
         } else {
             self.stopped = true;
         }
@@ -126,16 +331,113 @@ impl Computer {
         }
     }
 
-    fn find_quine(&mut self) -> usize {
+    /// Runs the program from the start with register `a` set to `a`, returning its output.
+    fn run(&mut self, a: usize) -> &[usize] {
+        self.a = a;
+        self.b = self.initial_b;
+        self.c = self.initial_c;
+        self.counter = 0;
+        self.stopped = false;
+        self.output.clear();
+
+        while !self.stopped {
+            self.step();
+        }
+        &self.output
+    }
+
+    fn instructions(&self) -> impl Iterator<Item = (Opcode, usize)> + '_ {
+        self.program
+            .chunks_exact(2)
+            .map(|instruction| (Opcode::from(instruction[0]), instruction[1]))
+    }
+
+    fn disassemble(&self) -> String {
+        self.instructions()
+            .enumerate()
+            .map(|(index, (opcode, operand))| {
+                format!("{:02}: {}\n", index * 2, opcode.assembly(operand))
+            })
+            .collect()
+    }
+
+    /// Symbolically executes one iteration of the loop, see [`Computer::check_loop_shape`].
+    fn decompile(&self) -> Result<LoopBody, QuineError> {
+        self.check_loop_shape()?;
+
+        let mut a = Expr::Register('a');
+        let mut b = Expr::Register('b');
+        let mut c = Expr::Register('c');
+        let mut output = Expr::Literal(0);
+
+        for (opcode, operand) in self.instructions() {
+            // 7 is only reserved as a combo operand, literal ones like `bxl 7` are fine
+            let operand = match OperandType::from(opcode) {
+                OperandType::Literal => Expr::Literal(operand),
+                OperandType::Combo => match operand {
+                    0..=3 => Expr::Literal(operand),
+                    4 => a.clone(),
+                    5 => b.clone(),
+                    6 => c.clone(),
+                    _ => unreachable!(),
+                },
+            };
+            match opcode {
+                Opcode::ADV => a = a.shr(operand),
+                Opcode::BXL => b = b.xor(operand),
+                Opcode::BST => b = operand.mod8(),
+                Opcode::JNZ => (),
+                Opcode::BXC => b = b.xor(c.clone()),
+                Opcode::OUT => output = operand.mod8(),
+                Opcode::BDV => b = a.clone().shr(operand),
+                Opcode::CDV => c = a.clone().shr(operand),
+            }
+        }
+
+        Ok(LoopBody { output, next_a: a })
+    }
+
+    /// The search relies on each loop iteration outputting one digit and dropping the low 3 bits
+    /// of `a`, so that the last iteration only sees the highest 3 bits.
+    fn check_loop_shape(&self) -> Result<(), QuineError> {
+        let count = |opcode| {
+            self.instructions()
+                .filter(|(instruction, _)| *instruction == opcode)
+                .count()
+        };
+
+        if !self.program.len().is_multiple_of(2)
+            || count(Opcode::JNZ) != 1
+            || self.instructions().last() != Some((Opcode::JNZ, 0))
+        {
+            return Err(QuineError::NotSingleLoop);
+        }
+
+        if count(Opcode::ADV) != 1 || !self.instructions().contains(&(Opcode::ADV, 3)) {
+            return Err(QuineError::ShiftNotThree);
+        }
+
+        match count(Opcode::OUT) {
+            1 => Ok(()),
+            outputs => Err(QuineError::OutputCount(outputs)),
+        }
+    }
+
+    /// Builds `a` up 3 bits at a time from the last output digit backwards, keeping every
+    /// candidate whose run reproduces the tail of the program.
+    fn find_quine(&mut self) -> Result<usize, QuineError> {
+        self.check_loop_shape()?;
+
         let mut quines = FxHashSet::default();
         quines.insert(0);
 
-        for num in self.program.iter().rev() {
+        for position in (0..self.program.len()).rev() {
             let mut new_quines = FxHashSet::default();
             for current in quines {
                 for i in 0..8 {
                     let new = (current << 3) + i;
-                    if Self::output(new) == *num {
+                    self.run(new);
+                    if self.output[..] == self.program[position..] {
                         new_quines.insert(new);
                     }
                 }
@@ -143,17 +445,22 @@ impl Computer {
             quines = new_quines;
         }
 
-        *quines.iter().min().unwrap_or(&0)
-    }
-
-    fn output(a: usize) -> usize {
-        let partial = (a % 8) ^ 1;
-        ((partial ^ (a >> partial)) ^ 4) % 8
+        quines.into_iter().min().ok_or(QuineError::NoQuine)
     }
 }
 
 pub fn part_one(input: &str) -> Option<String> {
     let mut computer = Computer::new(input);
+
+    if has_flag("--disasm") {
+        print!("{}", computer.disassemble());
+        match computer.decompile() {
+            Ok(body) => println!("{body}"),
+            Err(error) => println!("Cannot decompile: {error}"),
+        }
+    }
+    computer.trace = has_flag("--trace");
+
     while !computer.stopped {
         computer.step();
     }
@@ -163,7 +470,10 @@ pub fn part_one(input: &str) -> Option<String> {
 pub fn part_two(input: &str) -> Option<usize> {
     let mut computer = Computer::new(input);
 
-    Some(computer.find_quine())
+    computer
+        .find_quine()
+        .map_err(|error| eprintln!("Cannot find quine: {error}"))
+        .ok()
 }
 
 #[cfg(test)]
@@ -179,6 +489,84 @@ mod tests {
     #[test]
     fn test_part_two() {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
-        assert_eq!(result, Some(0));
+        assert_eq!(result, Some(117440));
+    }
+
+    #[test]
+    fn test_find_quine_other_program() {
+        let input = "Register A: 0\nRegister B: 0\nRegister C: 0\n\nProgram: 2,4,1,1,7,5,1,5,4,0,0,3,5,5,3,0";
+        let mut computer = Computer::new(input);
+        let a = computer.find_quine().unwrap();
+        let program = computer.program.clone();
+        assert_eq!(computer.run(a), program);
+        assert_ne!(computer.run(a - 1), program);
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let computer = Computer::new(&advent_of_code::template::read_file("examples", DAY));
+        assert_eq!(
+            computer.disassemble(),
+            "00: a >>= 3\n02: out a % 8\n04: jnz 0\n"
+        );
+    }
+
+    #[test]
+    fn test_decompile_matches_interpreter() {
+        let programs = [
+            "0,3,5,4,3,0",
+            "2,4,1,1,7,5,1,5,4,0,0,3,5,5,3,0",
+            "2,4,1,3,7,5,0,3,1,5,4,4,5,5,3,0",
+            "2,4,1,7,7,5,1,7,4,4,5,5,0,3,3,0",
+        ];
+        for program in programs {
+            let mut computer = Computer::new(&format!(
+                "Register A: 0\nRegister B: 0\nRegister C: 0\n\nProgram: {program}"
+            ));
+            let body = computer.decompile().unwrap();
+            for a in (1..5000).chain([0o1234567012345, 0o7777777777]) {
+                let expected = computer.run(a)[0];
+                assert_eq!(
+                    body.output.eval(a, 0, 0),
+                    expected,
+                    "{program} with a = {a}"
+                );
+                assert_eq!(body.next_a.eval(a, 0, 0), a >> 3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decompile_closed_form() {
+        let computer = Computer::new(
+            "Register A: 0\nRegister B: 0\nRegister C: 0\n\nProgram: 2,4,1,1,7,5,1,5,4,0,0,3,5,5,3,0",
+        );
+        let body = computer.decompile().unwrap();
+        assert_eq!(
+            body.output.to_string(),
+            "((a % 8) ^ (a >> ((a % 8) ^ 1)) ^ 4) % 8"
+        );
+        assert_eq!(body.next_a.to_string(), "a >> 3");
+    }
+
+    #[test]
+    fn test_find_quine_rejects_shape() {
+        let program = |program: &str| {
+            Computer::new(&format!(
+                "Register A: 0\nRegister B: 0\nRegister C: 0\n\nProgram: {program}"
+            ))
+        };
+        assert_eq!(
+            program("0,3,5,4,3,2").find_quine(),
+            Err(QuineError::NotSingleLoop)
+        );
+        assert_eq!(
+            program("0,1,5,4,3,0").find_quine(),
+            Err(QuineError::ShiftNotThree)
+        );
+        assert_eq!(
+            program("0,3,5,4,5,4,3,0").find_quine(),
+            Err(QuineError::OutputCount(2))
+        );
     }
 }
@@ -1,18 +1,26 @@
 use std::ops::Add;
 
+use advent_of_code::template::flag_value;
 use pathfinding::prelude::dijkstra;
 
-#[cfg(test)]
-const GRID_SIZE: usize = 7;
-#[cfg(not(test))]
-const GRID_SIZE: usize = 71;
+advent_of_code::solution!(18);
 
-#[cfg(test)]
-const NUM_BRICKS: usize = 12;
-#[cfg(not(test))]
-const NUM_BRICKS: usize = 1024;
+/// The square memory grid and how many bytes have fallen into it when the search starts.
+#[derive(Debug, Clone, Copy)]
+struct MemorySpace {
+    size: usize,
+    num_bricks: usize,
+}
 
-advent_of_code::solution!(18);
+const PUZZLE_SPACE: MemorySpace = MemorySpace {
+    size: 71,
+    num_bricks: 1024,
+};
+
+const EXAMPLE_SPACE: MemorySpace = MemorySpace {
+    size: 7,
+    num_bricks: 12,
+};
 
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
 struct Point {
@@ -88,8 +96,8 @@ fn get_successors(point: &Point, grid: &[Vec<Memory>]) -> Vec<(Point, u32)> {
 
 fn find_path(grid: &[Vec<Memory>]) -> Option<(Vec<Point>, u32)> {
     let end = Point {
-        x: (GRID_SIZE - 1) as i32,
-        y: (GRID_SIZE - 1) as i32,
+        x: (grid.len() - 1) as i32,
+        y: (grid.len() - 1) as i32,
     };
     dijkstra(
         &Point { x: 0, y: 0 },
@@ -98,32 +106,140 @@ fn find_path(grid: &[Vec<Memory>]) -> Option<(Vec<Point>, u32)> {
     )
 }
 
-pub fn part_one(input: &str) -> Option<u32> {
-    let points = parse(input);
+/// Disjoint sets over grid cells, with path halving and union by size.
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
 
-    let mut grid = vec![vec![Memory::Normal; GRID_SIZE]; GRID_SIZE];
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
 
-    corrupt_memory(&mut grid, &points, NUM_BRICKS);
-    Some(find_path(&grid).unwrap().1)
+    fn find(&mut self, mut cell: usize) -> usize {
+        while self.parent[cell] != cell {
+            self.parent[cell] = self.parent[self.parent[cell]];
+            cell = self.parent[cell];
+        }
+        cell
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
 }
 
-pub fn part_two(input: &str) -> Option<String> {
-    let points = parse(input);
+fn shortest_path(points: &[Point], space: MemorySpace) -> Option<u32> {
+    let mut grid = vec![vec![Memory::Normal; space.size]; space.size];
+
+    corrupt_memory(&mut grid, points, space.num_bricks);
+    find_path(&grid).map(|(_, steps)| steps)
+}
+
+/// The first byte after `space.num_bricks` that cuts the start off from the exit.
+///
+/// Works backwards from every byte having fallen: cells are cleared again in reverse order and
+/// joined to their free neighbours, and the byte whose removal first connects the corners is the
+/// one that blocked them.
+fn first_blocking_byte(points: &[Point], space: MemorySpace) -> Option<Point> {
+    let size = space.size;
+    let index = |point: &Point| point.y as usize * size + point.x as usize;
+
+    // when each cell gets corrupted, if ever
+    let mut fallen_at = vec![usize::MAX; size * size];
+    for (time, point) in points.iter().enumerate().rev() {
+        fallen_at[index(point)] = time;
+    }
+
+    let mut cells = UnionFind::new(size * size);
+    let mut free = vec![false; size * size];
+    let clear = |cell: usize, free: &mut [bool], cells: &mut UnionFind| {
+        free[cell] = true;
+        let point = Point {
+            x: (cell % size) as i32,
+            y: (cell / size) as i32,
+        };
+        for neighbour in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let (x, y) = (point.x + neighbour.0, point.y + neighbour.1);
+            if x >= 0 && y >= 0 && (x as usize) < size && (y as usize) < size {
+                let neighbour = y as usize * size + x as usize;
+                if free[neighbour] {
+                    cells.union(cell, neighbour);
+                }
+            }
+        }
+    };
+
+    for (cell, &time) in fallen_at.iter().enumerate() {
+        if time == usize::MAX {
+            clear(cell, &mut free, &mut cells);
+        }
+    }
+
+    let (start, end) = (0, size * size - 1);
+    if cells.find(start) == cells.find(end) {
+        return None;
+    }
 
-    let mut grid = vec![vec![Memory::Normal; GRID_SIZE]; GRID_SIZE];
+    for time in (space.num_bricks..points.len()).rev() {
+        let cell = index(&points[time]);
+        if fallen_at[cell] != time {
+            // a repeat of an earlier byte, which changes nothing
+            continue;
+        }
+        clear(cell, &mut free, &mut cells);
+        if free[start] && free[end] && cells.find(start) == cells.find(end) {
+            return Some(points[time]);
+        }
+    }
 
-    let mut num_bricks = NUM_BRICKS;
+    None
+}
 
-    while find_path(&grid).is_some() {
-        num_bricks += 1;
-        corrupt_memory(&mut grid, &points, num_bricks);
+/// The memory space given with `--space <size>,<bytes>`, or else the smaller of the example and
+/// puzzle spaces that every byte falls inside.
+fn memory_space(points: &[Point]) -> MemorySpace {
+    if let Some(space) = flag_value("--space") {
+        let (size, num_bricks) = space.split_once(',').expect("--space takes <size>,<bytes>");
+        return MemorySpace {
+            size: size.parse().expect("Invalid memory size"),
+            num_bricks: num_bricks.parse().expect("Invalid byte count"),
+        };
     }
 
-    Some(format!(
-        "{},{}",
-        points[num_bricks - 1].x,
-        points[num_bricks - 1].y
-    ))
+    [EXAMPLE_SPACE, PUZZLE_SPACE]
+        .into_iter()
+        .find(|space| {
+            points
+                .iter()
+                .all(|point| point.x < space.size as i32 && point.y < space.size as i32)
+        })
+        .unwrap_or(PUZZLE_SPACE)
+}
+
+pub fn part_one(input: &str) -> Option<u32> {
+    let points = parse(input);
+
+    shortest_path(&points, memory_space(&points))
+}
+
+pub fn part_two(input: &str) -> Option<String> {
+    let points = parse(input);
+
+    first_blocking_byte(&points, memory_space(&points))
+        .map(|point| format!("{},{}", point.x, point.y))
 }
 
 #[cfg(test)]
@@ -141,4 +257,41 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some("6,1".into()));
     }
+
+    #[test]
+    fn test_first_blocking_byte_matches_search() {
+        let generate = |rng: &mut advent_of_code::random::Rng| {
+            let space = MemorySpace {
+                size: 15,
+                num_bricks: rng.below(40) as usize,
+            };
+            // bytes never land on the start or the exit
+            let points: Vec<_> = (0..200)
+                .map(|_| Point {
+                    x: rng.below(15) as i32,
+                    y: rng.below(15) as i32,
+                })
+                .filter(|point| ![(0, 0), (14, 14)].contains(&(point.x, point.y)))
+                .collect();
+            (space, points)
+        };
+        let search = |(space, points): &(MemorySpace, Vec<Point>)| {
+            let mut grid = vec![vec![Memory::Normal; space.size]; space.size];
+            corrupt_memory(&mut grid, points, space.num_bricks);
+            // already blocked before the search starts
+            find_path(&grid)?;
+            (space.num_bricks..points.len()).find_map(|time| {
+                corrupt_memory(&mut grid, points, time + 1);
+                find_path(&grid).is_none().then_some(points[time])
+            })
+        };
+
+        advent_of_code::random::cross_check(
+            18,
+            20,
+            generate,
+            |(space, points)| first_blocking_byte(points, *space),
+            search,
+        );
+    }
 }
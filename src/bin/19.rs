@@ -1,108 +1,166 @@
-use std::collections::BinaryHeap;
-
-use fxhash::FxHashMap;
+use advent_of_code::template::has_flag;
 
 advent_of_code::solution!(19);
 
-#[derive(Default)]
-struct Trie(bool, FxHashMap<u8, Trie>);
-
-fn add_to_tree(pattern: &[u8], Trie(is_leaf, child): &mut Trie) {
-    match pattern {
-        [] => *is_leaf = true,
-        [front, rest @ ..] => {
-            if let Some(sub_tree) = child.get_mut(front) {
-                add_to_tree(rest, sub_tree);
-            } else {
-                let mut new_trie = Trie::default();
-                add_to_tree(rest, &mut new_trie);
-                child.insert(*front, new_trie);
-            }
-        }
+const COLOURS: usize = 5;
+const NO_CHILD: u32 = 0;
+
+fn colour(stripe: u8) -> Option<usize> {
+    match stripe {
+        b'w' => Some(0),
+        b'u' => Some(1),
+        b'b' => Some(2),
+        b'r' => Some(3),
+        b'g' => Some(4),
+        _ => None,
     }
 }
 
-fn build_pattern_tree(patterns: &[Vec<u8>]) -> Trie {
-    let mut tree = Trie::default();
-    patterns
-        .iter()
-        .for_each(|pattern| add_to_tree(pattern, &mut tree));
-    tree
+/// The available towels as a trie over the five colours, stored as arrays of node indices. The
+/// root is node 0, so an index of 0 can mark a missing child.
+struct Towels {
+    children: Vec<[u32; COLOURS]>,
+    is_towel: Vec<bool>,
 }
 
-fn trie_search(pattern: &[u8], Trie(is_leaf, child): &Trie, depth: usize) -> Vec<usize> {
-    let mut sub_pattern_lengths = match pattern {
-        [] => vec![],
-        [first, rest @ ..] => child
-            .get(first)
-            .map(|child| trie_search(rest, child, depth + 1))
-            .unwrap_or_default(),
-    };
-    if *is_leaf {
-        sub_pattern_lengths.push(depth);
+impl Towels {
+    fn new(towels: &[&[u8]]) -> Self {
+        let mut trie = Self {
+            children: vec![[NO_CHILD; COLOURS]],
+            is_towel: vec![false],
+        };
+
+        for towel in towels {
+            let mut node = 0;
+            for &stripe in *towel {
+                let colour = colour(stripe).expect("Invalid colour");
+                if trie.children[node][colour] == NO_CHILD {
+                    trie.children[node][colour] = trie.children.len() as u32;
+                    trie.children.push([NO_CHILD; COLOURS]);
+                    trie.is_towel.push(false);
+                }
+                node = trie.children[node][colour] as usize;
+            }
+            trie.is_towel[node] = true;
+        }
+        trie
     }
-    sub_pattern_lengths
-}
 
-fn is_possible(pattern: &[u8], available: &Trie) -> bool {
-    let mut stack = BinaryHeap::from([0usize]);
-    let mut found = false;
-    while let Some(read_length) = stack.pop() {
-        if read_length == pattern.len() {
-            found = true;
-            break;
+    /// Calls `found` with the end of every towel that matches `design` from `start`.
+    fn for_each_match(&self, design: &[u8], start: usize, mut found: impl FnMut(usize)) {
+        let mut node = 0;
+        for (end, &stripe) in design.iter().enumerate().skip(start) {
+            let Some(colour) = colour(stripe) else {
+                return;
+            };
+            node = self.children[node][colour] as usize;
+            if node == NO_CHILD as usize {
+                return;
+            }
+            if self.is_towel[node] {
+                found(end + 1);
+            }
+        }
+    }
+
+    /// Counts the ways to make `design` and finds one of them, in a single pass over its prefixes.
+    fn arrange<'a>(&self, design: &'a [u8]) -> Arrangement<'a> {
+        let mut ways = vec![0; design.len() + 1];
+        // the start of the last towel in one way of making each prefix
+        let mut last_towel = vec![None; design.len() + 1];
+        ways[0] = 1;
+
+        for start in 0..design.len() {
+            if ways[start] == 0 {
+                continue;
+            }
+            self.for_each_match(design, start, |end| {
+                ways[end] += ways[start];
+                last_towel[end].get_or_insert(start);
+            });
+        }
+
+        Arrangement {
+            design,
+            ways: ways[design.len()],
+            last_towel,
         }
-        stack.extend(trie_search(&pattern[read_length..], available, read_length));
     }
-    found
 }
 
-fn possible_counts(pattern: &[u8], available: &Trie) -> usize {
-    let mut counts = [vec![1], vec![0; pattern.len()]].concat();
-    for idx in 0..counts.len() {
-        for possible_end in trie_search(&pattern[idx..], available, idx) {
-            counts[possible_end] += counts[idx];
+struct Arrangement<'a> {
+    design: &'a [u8],
+    ways: u64,
+    last_towel: Vec<Option<usize>>,
+}
+
+impl<'a> Arrangement<'a> {
+    /// One way of making the design, or nothing if there is none.
+    fn towels(&self) -> Vec<&'a [u8]> {
+        let mut towels = vec![];
+        let mut end = self.design.len();
+        while let Some(start) = self.last_towel[end] {
+            towels.push(&self.design[start..end]);
+            end = start;
         }
+        towels.reverse();
+        towels
     }
-    *counts.last().unwrap()
 }
 
-fn parse(input: &str) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+fn parse(input: &str) -> (Vec<&[u8]>, Vec<&[u8]>) {
     let (available_towels, requested_patterns) = input.trim_end().split_once("\n\n").unwrap();
 
-    let available = available_towels
-        .split(", ")
-        .map(|char| char.bytes().collect())
-        .collect();
-    let requested = requested_patterns
-        .lines()
-        .map(|line| line.bytes().collect())
-        .collect();
+    let available = available_towels.split(", ").map(str::as_bytes).collect();
+    let requested = requested_patterns.lines().map(str::as_bytes).collect();
 
     (available, requested)
 }
 
-pub fn part_one(input: &str) -> Option<usize> {
-    let (available, requested) = parse(input);
-
-    let tree = build_pattern_tree(&available);
+fn describe(arrangement: &Arrangement) -> String {
+    let design = String::from_utf8_lossy(arrangement.design);
+    if arrangement.ways == 0 {
+        return format!("{design}: impossible");
+    }
 
-    let counts = requested
+    let towels: Vec<_> = arrangement
+        .towels()
         .iter()
-        .map(|request| is_possible(request, &tree))
-        .filter(|a| *a)
-        .count();
-    Some(counts)
+        .map(|towel| String::from_utf8_lossy(towel))
+        .collect();
+    format!(
+        "{design}: {} (one of {})",
+        towels.join(", "),
+        arrangement.ways
+    )
+}
+
+pub fn part_one(input: &str) -> Option<usize> {
+    let (available, requested) = parse(input);
+    let towels = Towels::new(&available);
+
+    let decompose = has_flag("--decompose");
+    let mut possible = 0;
+    for design in requested {
+        let arrangement = towels.arrange(design);
+        if decompose {
+            println!("{}", describe(&arrangement));
+        }
+        if arrangement.ways > 0 {
+            possible += 1;
+        }
+    }
+    Some(possible)
 }
 
-pub fn part_two(input: &str) -> Option<usize> {
+pub fn part_two(input: &str) -> Option<u64> {
     let (available, requested) = parse(input);
+    let towels = Towels::new(&available);
 
-    let tree = build_pattern_tree(&available);
     Some(
         requested
             .iter()
-            .map(|wanted| possible_counts(wanted, &tree))
+            .map(|design| towels.arrange(design).ways)
             .sum(),
     )
 }
@@ -122,4 +180,36 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(16));
     }
+
+    #[test]
+    fn test_arrange() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let (available, requested) = parse(&input);
+        let towels = Towels::new(&available);
+
+        let descriptions: Vec<String> = requested
+            .iter()
+            .map(|design| describe(&towels.arrange(design)))
+            .collect();
+        assert_eq!(
+            descriptions,
+            [
+                "brwrr: br, wr, r (one of 2)",
+                "bggr: b, g, g, r (one of 1)",
+                "gbbr: gb, br (one of 4)",
+                "rrbgbr: r, rb, g, br (one of 6)",
+                "ubwu: impossible",
+                "bwurrg: bwu, r, r, g (one of 1)",
+                "brgr: br, g, r (one of 2)",
+                "bbrgwb: impossible",
+            ]
+        );
+
+        for design in requested {
+            let arrangement = towels.arrange(design);
+            if arrangement.ways > 0 {
+                assert_eq!(arrangement.towels().concat(), design);
+            }
+        }
+    }
 }
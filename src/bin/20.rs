@@ -1,6 +1,9 @@
-use fxhash::FxHashMap;
-use itertools::Itertools;
-use std::{collections::VecDeque, ops::Add};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    ops::Add,
+};
+
+use advent_of_code::template::has_flag;
 
 advent_of_code::solution!(20);
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
@@ -25,10 +28,8 @@ enum Cell {
     Wall,
 }
 
-#[inline]
-fn taxicab_distance(point0: Point, point1: Point) -> u32 {
-    point0.x.abs_diff(point1.x) + point0.y.abs_diff(point1.y)
-}
+/// Cheats must save at least this many picoseconds to count.
+const THRESHOLD: u32 = 100;
 
 fn get(grid: &[Vec<Cell>], x: i32, y: i32) -> Cell {
     grid[y as usize][x as usize]
@@ -83,17 +84,19 @@ fn parse(input: &str) -> (Vec<Vec<Cell>>, Point, Point) {
     (grid, start, end)
 }
 
-fn get_distances(start: &Point, end: &Point, grid: &[Vec<Cell>]) -> FxHashMap<Point, u32> {
-    let mut distances = FxHashMap::default();
+/// How far each track cell is from the start, `None` for walls.
+fn get_distances(start: &Point, end: &Point, grid: &[Vec<Cell>]) -> Vec<Vec<Option<u32>>> {
+    let mut distances = vec![vec![None; grid[0].len()]; grid.len()];
 
     let mut queue = VecDeque::from([(*start, 0)]);
 
     while let Some((point, distance)) = queue.pop_front() {
-        if distances.contains_key(&point) {
+        let known = &mut distances[point.y as usize][point.x as usize];
+        if known.is_some() {
             continue;
         }
 
-        distances.insert(point, distance);
+        *known = Some(distance);
 
         if point == *end {
             continue;
@@ -109,29 +112,101 @@ fn get_distances(start: &Point, end: &Point, grid: &[Vec<Cell>]) -> FxHashMap<Po
     distances
 }
 
-fn cheating_savings(start: &Point, end: &Point, grid: &[Vec<Cell>], cheat_distance: u32) -> u32 {
-    let distances = get_distances(start, end, grid);
+/// `counts[saving]` is the number of cheats of at most `radius` steps that save `saving`
+/// picoseconds. Every track cell checks the diamond of cells within `radius` around it for track
+/// further along.
+fn cheat_counts(distances: &[Vec<Option<u32>>], radius: u32) -> Vec<u32> {
+    let radius = radius as i32;
+    let offsets: Vec<(i32, i32, u32)> = (-radius..=radius)
+        .flat_map(|dy: i32| {
+            let width = radius - dy.abs();
+            (-width..=width).map(move |dx: i32| (dx, dy, dx.unsigned_abs() + dy.unsigned_abs()))
+        })
+        .collect();
 
-    let mut savings = 0;
-    for ((p1, c1), (p2, c2)) in distances.iter().tuple_combinations() {
-        let point_distance = taxicab_distance(*p1, *p2);
-        if c2.abs_diff(*c1) >= point_distance + 100 && point_distance <= cheat_distance {
-            savings += 1;
+    let longest = distances.iter().flatten().flatten().max().copied();
+    let mut counts = vec![0; longest.map_or(0, |longest| longest as usize + 1)];
+
+    let height = distances.len() as i32;
+    for (y, row) in distances.iter().enumerate() {
+        let width = row.len() as i32;
+        for (x, distance) in row.iter().enumerate() {
+            let Some(distance) = distance else {
+                continue;
+            };
+            for &(dx, dy, steps) in &offsets {
+                let (x, y) = (x as i32 + dx, y as i32 + dy);
+                if x < 0 || y < 0 || x >= width || y >= height {
+                    continue;
+                }
+                if let Some(other) = distances[y as usize][x as usize] {
+                    if other > distance + steps {
+                        counts[(other - distance - steps) as usize] += 1;
+                    }
+                }
+            }
         }
     }
-    savings
+
+    counts
+}
+
+/// How many cheats save each amount of time, like the tables in the puzzle.
+fn cheat_histogram(
+    start: &Point,
+    end: &Point,
+    grid: &[Vec<Cell>],
+    radius: u32,
+) -> BTreeMap<u32, u32> {
+    let distances = get_distances(start, end, grid);
+
+    cheat_counts(&distances, radius)
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, count)| count > 0)
+        .map(|(saving, count)| (saving as u32, count))
+        .collect()
+}
+
+fn cheating_savings(
+    start: &Point,
+    end: &Point,
+    grid: &[Vec<Cell>],
+    radius: u32,
+    threshold: u32,
+) -> u32 {
+    let distances = get_distances(start, end, grid);
+
+    cheat_counts(&distances, radius)
+        .iter()
+        .skip(threshold as usize)
+        .sum()
+}
+
+fn print_histogram(histogram: &BTreeMap<u32, u32>) {
+    for (saving, count) in histogram {
+        println!("{count} cheats save {saving} picoseconds");
+    }
 }
 
 pub fn part_one(input: &str) -> Option<u32> {
     let (grid, start, end) = parse(input);
 
-    Some(cheating_savings(&start, &end, &grid, 2))
+    if has_flag("--histogram") {
+        print_histogram(&cheat_histogram(&start, &end, &grid, 2));
+    }
+
+    Some(cheating_savings(&start, &end, &grid, 2, THRESHOLD))
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
     let (grid, start, end) = parse(input);
 
-    Some(cheating_savings(&start, &end, &grid, 20))
+    if has_flag("--histogram") {
+        print_histogram(&cheat_histogram(&start, &end, &grid, 20));
+    }
+
+    Some(cheating_savings(&start, &end, &grid, 20, THRESHOLD))
 }
 
 #[cfg(test)]
@@ -149,4 +224,54 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(0));
     }
+
+    #[test]
+    fn test_histogram_two_steps() {
+        let (grid, start, end) = parse(&advent_of_code::template::read_file("examples", DAY));
+        let expected = BTreeMap::from([
+            (2, 14),
+            (4, 14),
+            (6, 2),
+            (8, 4),
+            (10, 2),
+            (12, 3),
+            (20, 1),
+            (36, 1),
+            (38, 1),
+            (40, 1),
+            (64, 1),
+        ]);
+        assert_eq!(cheat_histogram(&start, &end, &grid, 2), expected);
+        assert_eq!(cheating_savings(&start, &end, &grid, 2, 20), 5);
+    }
+
+    #[test]
+    fn test_histogram_twenty_steps() {
+        let (grid, start, end) = parse(&advent_of_code::template::read_file("examples", DAY));
+        let expected = BTreeMap::from([
+            (50, 32),
+            (52, 31),
+            (54, 29),
+            (56, 39),
+            (58, 25),
+            (60, 23),
+            (62, 20),
+            (64, 19),
+            (66, 12),
+            (68, 14),
+            (70, 12),
+            (72, 22),
+            (74, 4),
+            (76, 3),
+        ]);
+        let histogram = cheat_histogram(&start, &end, &grid, 20);
+        assert_eq!(
+            histogram
+                .range(50..)
+                .map(|(&k, &v)| (k, v))
+                .collect::<BTreeMap<_, _>>(),
+            expected
+        );
+        assert_eq!(cheating_savings(&start, &end, &grid, 20, 76), 3);
+    }
 }
@@ -1,3 +1,4 @@
+use advent_of_code::template::{flag_value, has_flag};
 use fxhash::FxHashMap;
 use std::str;
 
@@ -80,52 +81,107 @@ fn sequence_counts(
         })
 }
 
-fn complexity(
-    codes: &[Vec<u8>],
-    num_bots: u32,
+/// The buttons to press on your own keypad to type `code` through `robots` robot-operated
+/// directional keypads. The sequence grows exponentially with `robots`, so this is only for small
+/// depths; [`sequence_length`] handles any depth.
+fn shortest_sequence(
+    code: &[u8],
+    robots: usize,
+    numpad_lookup: &FxHashMap<u8, (i32, i32)>,
+    dirpad_lookup: &FxHashMap<u8, (i32, i32)>,
+) -> Vec<u8> {
+    let mut sequence = sequences(code, numpad_lookup).concat();
+    for _ in 0..robots {
+        sequence = sequences(&sequence, dirpad_lookup).concat();
+    }
+    sequence
+}
+
+/// Length of [`shortest_sequence`], tracking how often each move occurs instead of the moves
+/// themselves.
+fn sequence_length(
+    code: &[u8],
+    robots: usize,
     numpad_lookup: &FxHashMap<u8, (i32, i32)>,
     dirpad_lookup: &FxHashMap<u8, (i32, i32)>,
 ) -> usize {
-    let mut frequency_tables: Vec<FxHashMap<Vec<u8>, usize>> = codes
-        .iter()
-        .map(|code| sequences(code, numpad_lookup).concat())
-        .map(|seq| {
-            let mut temp = FxHashMap::default();
-            temp.insert(seq, 1);
-            temp
-        })
-        .collect();
-
-    for _ in 0..num_bots {
-        frequency_tables = frequency_tables
-            .iter()
-            .map(|frequency_table| {
-                let mut sub_freq_table: FxHashMap<Vec<u8>, usize> = FxHashMap::default();
-                frequency_table.iter().for_each(|(seq, freq)| {
-                    sequence_counts(seq, dirpad_lookup).into_iter().for_each(
-                        |(sub_seq, sub_freq)| {
-                            *sub_freq_table.entry(sub_seq).or_default() += sub_freq * freq;
-                        },
-                    );
+    let mut frequency_table = FxHashMap::default();
+    frequency_table.insert(sequences(code, numpad_lookup).concat(), 1);
+
+    for _ in 0..robots {
+        let mut sub_freq_table: FxHashMap<Vec<u8>, usize> = FxHashMap::default();
+        frequency_table.iter().for_each(|(seq, freq)| {
+            sequence_counts(seq, dirpad_lookup)
+                .into_iter()
+                .for_each(|(sub_seq, sub_freq)| {
+                    *sub_freq_table.entry(sub_seq).or_default() += sub_freq * freq;
                 });
-                sub_freq_table
-            })
-            .collect();
+        });
+        frequency_table = sub_freq_table;
     }
 
-    frequency_tables
+    frequency_table
         .iter()
-        .zip(codes)
-        .map(|(seqs, codes)| {
-            let temp = str::from_utf8(&codes[..codes.len() - 1])
-                .unwrap()
-                .parse::<usize>()
-                .unwrap();
-            temp * seqs
-                .iter()
-                .map(|(seq, freq)| seq.len() * freq)
-                .sum::<usize>()
-        })
+        .map(|(seq, freq)| seq.len() * freq)
+        .sum()
+}
+
+/// Presses `presses` on `pad` with the arm starting over `A`, returning the keys it activates.
+/// Returns `None` if the arm would point at the gap or leave the pad.
+fn type_through(presses: &[u8], pad: &[[u8; 3]]) -> Option<Vec<u8>> {
+    let (mut x, mut y) = pad.iter().enumerate().find_map(|(y, row)| {
+        let x = row.iter().position(|&key| key == b'A')?;
+        Some((x as i32, y as i32))
+    })?;
+
+    let mut output = vec![];
+    for &press in presses {
+        let (dx, dy) = match press {
+            b'^' => (0, -1),
+            b'v' => (0, 1),
+            b'<' => (-1, 0),
+            b'>' => (1, 0),
+            b'A' => {
+                output.push(pad[y as usize][x as usize]);
+                continue;
+            }
+            _ => return None,
+        };
+        (x, y) = (x + dx, y + dy);
+
+        let key = pad.get(y as usize)?.get(x as usize)?;
+        if *key == b' ' {
+            return None;
+        }
+    }
+    Some(output)
+}
+
+/// Whether `presses` on your own keypad make the chain of `robots` directional keypads type
+/// `code` on the numeric keypad.
+fn types_code(presses: &[u8], robots: usize, code: &[u8]) -> bool {
+    (0..robots)
+        .try_fold(presses.to_vec(), |typed, _| type_through(&typed, &DIRPAD))
+        .and_then(|typed| type_through(&typed, &NUMPAD))
+        .is_some_and(|typed| typed == code)
+}
+
+fn code_value(code: &[u8]) -> usize {
+    str::from_utf8(&code[..code.len() - 1])
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+fn complexity(
+    codes: &[Vec<u8>],
+    robots: usize,
+    numpad_lookup: &FxHashMap<u8, (i32, i32)>,
+    dirpad_lookup: &FxHashMap<u8, (i32, i32)>,
+) -> usize {
+    codes
+        .iter()
+        .map(|code| code_value(code) * sequence_length(code, robots, numpad_lookup, dirpad_lookup))
         .sum()
 }
 
@@ -137,32 +193,51 @@ fn parse(input: &str) -> Vec<Vec<u8>> {
         .collect()
 }
 
+/// The most robots `--sequences` prints the presses for. Each robot makes the sequence about two
+/// and a half times longer, so this is already hundreds of thousands of presses per code.
+const MAX_PRINTED_ROBOTS: usize = 10;
+
+fn print_sequences(
+    codes: &[Vec<u8>],
+    robots: usize,
+    numpad_lookup: &FxHashMap<u8, (i32, i32)>,
+    dirpad_lookup: &FxHashMap<u8, (i32, i32)>,
+) {
+    if robots > MAX_PRINTED_ROBOTS {
+        eprintln!("Cannot print sequences through {robots} robots, at most {MAX_PRINTED_ROBOTS}");
+        return;
+    }
+
+    for code in codes {
+        let sequence = shortest_sequence(code, robots, numpad_lookup, dirpad_lookup);
+        debug_assert!(types_code(&sequence, robots, code));
+        println!(
+            "{}: {}",
+            str::from_utf8(code).unwrap(),
+            str::from_utf8(&sequence).unwrap()
+        );
+    }
+}
+
+/// Robots between you and the numeric keypad, overridable with `--robots <n>`.
+fn robots(default: usize) -> usize {
+    flag_value("--robots").map_or(default, |robots| {
+        robots.parse().expect("--robots takes a number")
+    })
+}
+
 pub fn part_one(input: &str) -> Option<usize> {
     let numpad_lookup = construct_mapping(&NUMPAD);
     let dirpad_lookup = construct_mapping(&DIRPAD);
 
     let codes = parse(input);
+    let robots = robots(2);
 
-    let sequences: Vec<Vec<u8>> = codes
-        .iter()
-        .map(|code| sequences(code, &numpad_lookup).concat())
-        .map(|robot1_seq| sequences(&robot1_seq, &dirpad_lookup).concat())
-        .map(|robot2_seq| sequences(&robot2_seq, &dirpad_lookup).concat())
-        .collect();
-
-    Some(
-        codes
-            .iter()
-            .zip(sequences)
-            .map(|(codes, seq)| {
-                let temp = str::from_utf8(&codes[..codes.len() - 1])
-                    .unwrap()
-                    .parse::<usize>()
-                    .unwrap();
-                temp * seq.len()
-            })
-            .sum(),
-    )
+    if has_flag("--sequences") {
+        print_sequences(&codes, robots, &numpad_lookup, &dirpad_lookup);
+    }
+
+    Some(complexity(&codes, robots, &numpad_lookup, &dirpad_lookup))
 }
 
 pub fn part_two(input: &str) -> Option<usize> {
@@ -171,7 +246,12 @@ pub fn part_two(input: &str) -> Option<usize> {
 
     let codes = parse(input);
 
-    Some(complexity(&codes, 25, &numpad_lookup, &dirpad_lookup))
+    Some(complexity(
+        &codes,
+        robots(25),
+        &numpad_lookup,
+        &dirpad_lookup,
+    ))
 }
 
 #[cfg(test)]
@@ -189,4 +269,93 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(154115708116294));
     }
+
+    /// The key the arm points at after moving from `key` in the direction of `press`.
+    fn move_arm(key: u8, press: u8, pad: &[[u8; 3]]) -> Option<u8> {
+        let lookup = construct_mapping(pad);
+        let (x, y) = lookup[&key];
+        let (x, y) = match press {
+            b'^' => (x, y - 1),
+            b'v' => (x, y + 1),
+            b'<' => (x - 1, y),
+            _ => (x + 1, y),
+        };
+        let key = *pad.get(y as usize)?.get(x as usize)?;
+        (key != b' ').then_some(key)
+    }
+
+    /// Length of the shortest way to type `code`, found by searching over every arm position
+    /// instead of trusting the move ordering in `shortest_path`.
+    fn brute_force_length(code: &[u8], robots: usize) -> usize {
+        let start = (vec![b'A'; robots + 1], 0);
+        let path = pathfinding::prelude::bfs(
+            &start,
+            |(arms, typed): &(Vec<u8>, usize)| {
+                b"^v<>A"
+                    .iter()
+                    .filter_map(|&press| {
+                        let mut arms = arms.clone();
+                        let mut press = press;
+                        // arms[0] is over the numeric keypad, arms[robots] is the one you control
+                        for level in (0..=robots).rev() {
+                            if press != b'A' {
+                                let pad: &[[u8; 3]] = if level == 0 { &NUMPAD } else { &DIRPAD };
+                                arms[level] = move_arm(arms[level], press, pad)?;
+                                return Some((arms, *typed));
+                            }
+                            press = arms[level];
+                        }
+                        (code.get(*typed) == Some(&press)).then(|| (arms, typed + 1))
+                    })
+                    .collect::<Vec<_>>()
+            },
+            |(_, typed)| *typed == code.len(),
+        );
+        path.unwrap().len() - 1
+    }
+
+    #[test]
+    fn test_shortest_sequence_types_code() {
+        let numpad_lookup = construct_mapping(&NUMPAD);
+        let dirpad_lookup = construct_mapping(&DIRPAD);
+
+        for code in parse(&advent_of_code::template::read_file("examples", DAY)) {
+            for robots in 0..=3 {
+                let sequence = shortest_sequence(&code, robots, &numpad_lookup, &dirpad_lookup);
+                assert!(types_code(&sequence, robots, &code));
+                assert_eq!(
+                    sequence.len(),
+                    sequence_length(&code, robots, &numpad_lookup, &dirpad_lookup)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_shortest_sequence_is_optimal() {
+        let numpad_lookup = construct_mapping(&NUMPAD);
+        let dirpad_lookup = construct_mapping(&DIRPAD);
+
+        let mut codes = parse(&advent_of_code::template::read_file("examples", DAY));
+        codes.extend([b"140A".to_vec(), b"703A".to_vec(), b"862A".to_vec()]);
+        for code in codes {
+            for robots in 0..=2 {
+                assert_eq!(
+                    sequence_length(&code, robots, &numpad_lookup, &dirpad_lookup),
+                    brute_force_length(&code, robots),
+                    "{} through {robots} robots",
+                    str::from_utf8(&code).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_types_code_rejects() {
+        assert!(types_code(b"<A^A>^^AvvvA", 0, b"029A"));
+        assert!(!types_code(b"<A^A>^^AvvvA", 0, b"028A"));
+        // would sweep the arm over the gap left of 0
+        assert!(!types_code(b"<<A", 0, b"0"));
+        assert!(!types_code(b"v<<A", 1, b"<"));
+    }
 }
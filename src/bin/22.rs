@@ -1,19 +1,27 @@
-use fxhash::FxHashMap;
 use rayon::prelude::*;
 
 advent_of_code::solution!(22);
 
+const STEPS: usize = 2000;
+/// Buyers whose secrets are generated side by side, so the loop can compile to SIMD. Each has a
+/// bit in the `u16` masks of `score_lane`.
+const LANES: usize = 16;
+/// A price change is in -9..=9, so four changes in a row encode to an index below 19^4.
+const WINDOWS: usize = 19 * 19 * 19 * 19;
+
 #[inline]
-fn mix(value: i64, seed: i64) -> i64 {
+fn mix(value: u32, seed: u32) -> u32 {
     value ^ seed
 }
 
 #[inline]
-fn prune(seed: i64) -> i64 {
+fn prune(seed: u32) -> u32 {
     seed & 0xFFFFFF
 }
 
-fn generate_secret(seed: i64) -> i64 {
+/// Shifting left can push bits past the top of a `u32`, but pruning drops them anyway.
+#[inline]
+fn generate_secret(seed: u32) -> u32 {
     let intermediate = prune(mix(seed << 6, seed));
 
     let intermediate = prune(mix(intermediate >> 5, intermediate));
@@ -21,7 +29,64 @@ fn generate_secret(seed: i64) -> i64 {
     prune(mix(intermediate << 11, intermediate))
 }
 
-fn parse(input: &str) -> Vec<i64> {
+#[inline]
+fn generate_secrets(secrets: &mut [u32; LANES]) {
+    for secret in secrets {
+        *secret = generate_secret(*secret);
+    }
+}
+
+/// Splits the buyers into full lanes, padding the last with zeros, which stay zero.
+fn lanes(secrets: &[u32]) -> Vec<[u32; LANES]> {
+    secrets
+        .chunks(LANES)
+        .map(|chunk| {
+            let mut lane = [0; LANES];
+            lane[..chunk.len()].copy_from_slice(chunk);
+            lane
+        })
+        .collect()
+}
+
+/// Adds to `scores` the price each buyer in `secrets` first sells at after every window of
+/// four changes. `seen` holds, per window, the stamp of the last lane group to sell on it and a
+/// mask of which of its buyers have, so it never needs clearing between groups.
+fn score_lane(
+    mut secrets: [u32; LANES],
+    buyers: usize,
+    stamp: u32,
+    scores: &mut [u32],
+    seen: &mut [(u32, u16)],
+) {
+    let mut prices = secrets.map(|secret| secret % 10);
+    let mut windows = [0; LANES];
+
+    for step in 0..STEPS {
+        generate_secrets(&mut secrets);
+
+        for lane in 0..buyers {
+            let price = secrets[lane] % 10;
+            let change = 9 + price - prices[lane];
+            prices[lane] = price;
+            windows[lane] = (windows[lane] * 19 + change as usize) % WINDOWS;
+
+            if step < 3 {
+                continue;
+            }
+            let (group, sold) = &mut seen[windows[lane]];
+            if *group != stamp {
+                *group = stamp;
+                *sold = 0;
+            }
+            if *sold & 1 << lane == 0 {
+                *sold |= 1 << lane;
+                scores[windows[lane]] += price;
+            }
+        }
+    }
+}
+
+fn parse(input: &str) -> Vec<u32> {
     input
         .trim_end()
         .lines()
@@ -29,71 +94,53 @@ fn parse(input: &str) -> Vec<i64> {
         .collect()
 }
 
-pub fn part_one(input: &str) -> Option<i64> {
-    let secret_bids = parse(input);
+pub fn part_one(input: &str) -> Option<u64> {
+    let secrets = parse(input);
 
     Some(
-        secret_bids
-            .par_iter()
-            .map(|&secret_bid| {
-                let mut bid = secret_bid;
-                for _ in 0..2000 {
-                    bid = generate_secret(bid);
+        lanes(&secrets)
+            .into_par_iter()
+            .map(|mut lane| {
+                for _ in 0..STEPS {
+                    generate_secrets(&mut lane);
                 }
-
-                bid
+                lane.iter().map(|&secret| secret as u64).sum::<u64>()
             })
             .sum(),
     )
 }
 
-pub fn part_two(input: &str) -> Option<i16> {
-    let secret_bids = parse(input);
-
-    secret_bids
-        .par_iter()
-        .map(|&secret_bid| {
-            let mut bids = Vec::with_capacity(2001);
-            let mut bid = secret_bid;
-            bids.push((bid % 10) as i16);
-            for _ in 0..2000 {
-                bid = generate_secret(bid);
-                bids.push((bid % 10) as i16);
+pub fn part_two(input: &str) -> Option<u32> {
+    let secrets = parse(input);
+
+    lanes(&secrets)
+        .into_par_iter()
+        .enumerate()
+        .fold(
+            || (vec![0; WINDOWS], vec![(0, 0); WINDOWS]),
+            |(mut scores, mut seen), (index, lane)| {
+                let buyers = LANES.min(secrets.len() - index * LANES);
+                // stamps start at 1, since 0 marks a window no group has sold on
+                let stamp = index as u32 + 1;
+                score_lane(lane, buyers, stamp, &mut scores, &mut seen);
+                (scores, seen)
+            },
+        )
+        .map(|(scores, _)| scores)
+        .reduce_with(|mut total, scores| {
+            for (total, score) in total.iter_mut().zip(scores) {
+                *total += score;
             }
-
-            let mut mapping = FxHashMap::default();
-
-            for window in bids.windows(5) {
-                let first = window[0];
-                let second = window[1];
-                let third = window[2];
-                let fourth = window[3];
-                let fifth = window[4];
-
-                mapping
-                    .entry((
-                        first - second,
-                        second - third,
-                        third - fourth,
-                        fourth - fifth,
-                    ))
-                    .or_insert(fifth);
-            }
-            mapping
-        })
-        .reduce(FxHashMap::default, |mut a, b| {
-            b.into_iter().for_each(|(k, v)| {
-                *a.entry(k).or_insert(0) += v;
-            });
-            a
-        })
-        .into_values()
+            total
+        })?
+        .into_iter()
         .max()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
 
     #[test]
     fn test_part_one() {
@@ -106,4 +153,70 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(23));
     }
+
+    #[test]
+    fn test_generate_secret() {
+        let secrets: Vec<u32> =
+            std::iter::successors(Some(123), |&secret| Some(generate_secret(secret)))
+                .skip(1)
+                .take(10)
+                .collect();
+        assert_eq!(
+            secrets,
+            [
+                15887950, 16495136, 527345, 704524, 1553684, 12683156, 11100544, 12249484, 7753432,
+                5908254
+            ]
+        );
+    }
+
+    /// Scores every window with a map per buyer, one buyer at a time.
+    fn best_price(secrets: &[u32]) -> u32 {
+        let mut totals: HashMap<[i8; 4], u32> = HashMap::new();
+
+        for &secret in secrets {
+            let prices: Vec<i8> =
+                std::iter::successors(Some(secret), |&secret| Some(generate_secret(secret)))
+                    .take(STEPS + 1)
+                    .map(|secret| (secret % 10) as i8)
+                    .collect();
+
+            let mut first_sale = HashMap::new();
+            for window in prices.windows(5) {
+                let changes = [1, 2, 3, 4].map(|index| window[index] - window[index - 1]);
+                first_sale.entry(changes).or_insert(window[4] as u32);
+            }
+            for (changes, price) in first_sale {
+                *totals.entry(changes).or_default() += price;
+            }
+        }
+
+        totals.into_values().max().unwrap()
+    }
+
+    #[test]
+    fn test_part_two_against_reference() {
+        // buyer counts around a full lane, so the padding and the lane masks both get exercised
+        let mut sizes = [1, 15, 16, 17, 40].into_iter();
+        let generate = |rng: &mut advent_of_code::random::Rng| -> Vec<u32> {
+            let buyers = sizes.next().unwrap();
+            (0..buyers).map(|_| 1 + rng.below(1 << 24) as u32).collect()
+        };
+        let solve = |secrets: &Vec<u32>| {
+            let input: Vec<String> = secrets.iter().map(u32::to_string).collect();
+            part_two(&input.join("\n"))
+        };
+
+        advent_of_code::random::cross_check(22, 5, generate, solve, |secrets| {
+            Some(best_price(secrets))
+        });
+    }
+
+    #[test]
+    fn test_part_two_past_u16_scores() {
+        // 9 bananas from each of this many buyers no longer fits in a u16
+        let buyers = u16::MAX as usize / 9 + 1;
+        let input = vec!["123"; buyers].join("\n");
+        assert_eq!(part_two(&input), Some(buyers as u32 * best_price(&[123])));
+    }
 }
@@ -1,114 +1,155 @@
-use std::{
-    collections::{HashMap, VecDeque},
-    iter::once,
-    rc::Rc,
-};
-
-use fxhash::{FxHashMap, FxHashSet};
+use advent_of_code::dense::BitSet;
 use itertools::Itertools;
 
 advent_of_code::solution!(23);
 
-type NodeList = FxHashSet<Rc<str>>;
-
-fn parse(input: &str) -> (FxHashMap<Rc<str>, NodeList>, NodeList) {
-    let mut adjacent: HashMap<Rc<str>, NodeList, _> = FxHashMap::default();
-    let mut tnames = FxHashSet::default();
-
-    input.trim_end().lines().for_each(|line| {
-        let (left, right) = line.split_once('-').unwrap();
-        let left: Rc<str> = left.into();
-        let right: Rc<str> = right.into();
-        adjacent
-            .entry(left.clone())
-            .or_default()
-            .insert(right.clone());
-        adjacent
-            .entry(right.clone())
-            .or_default()
-            .insert(left.clone());
-
-        if left.starts_with('t') {
-            tnames.insert(left);
-        }
-        if right.starts_with('t') {
-            tnames.insert(right);
-        }
-    });
+/// Every two-letter name gets its own id, so no lookup table is needed.
+const NAMES: usize = 26 * 26;
 
-    (adjacent, tnames)
+fn id(name: &str) -> usize {
+    let &[first, second] = name.as_bytes() else {
+        panic!("Invalid computer name {name}");
+    };
+    (first - b'a') as usize * 26 + (second - b'a') as usize
 }
 
-fn bron_kerbosch(
-    current: NodeList,
-    mut potential: NodeList,
-    mut processed: NodeList,
-    adjacent: &FxHashMap<Rc<str>, NodeList>,
-    result: &mut NodeList,
-) {
-    if potential.is_empty() && processed.is_empty() {
-        if current.len() > result.len() {
-            *result = current;
-        }
-        return;
-    }
-    for node in potential.clone() {
-        let neighbors = &adjacent[&node];
-        bron_kerbosch(
-            current.iter().cloned().chain(once(node.clone())).collect(),
-            potential.intersection(neighbors).cloned().collect(),
-            processed.intersection(neighbors).cloned().collect(),
-            adjacent,
-            result,
-        );
-        potential.remove(&node);
-        processed.insert(node);
-    }
+fn name(id: usize) -> String {
+    [
+        (b'a' + (id / 26) as u8) as char,
+        (b'a' + (id % 26) as u8) as char,
+    ]
+    .into_iter()
+    .collect()
 }
 
-pub fn part_one(input: &str) -> Option<usize> {
-    let (adjacent, tnames) = parse(input);
+/// The LAN as an adjacency bitset per computer id.
+struct Network {
+    computers: BitSet,
+    adjacent: Vec<BitSet>,
+}
 
-    let mut total = FxHashSet::default();
+impl Network {
+    fn new(input: &str) -> Self {
+        let mut network = Self {
+            computers: BitSet::new(NAMES),
+            adjacent: vec![BitSet::new(NAMES); NAMES],
+        };
+
+        for line in input.trim_end().lines() {
+            let (left, right) = line.split_once('-').unwrap();
+            let (left, right) = (id(left), id(right));
+            network.computers.insert(left);
+            network.computers.insert(right);
+            network.adjacent[left].insert(right);
+            network.adjacent[right].insert(left);
+        }
+        network
+    }
 
-    let mut queue: VecDeque<_> = tnames
-        .into_iter()
-        .map(|n| (n.clone(), vec![n.clone()]))
-        .collect();
+    /// Every set of `k` computers that are all connected to each other, each listed once in
+    /// ascending id order.
+    fn k_cliques(&self, k: usize) -> Vec<Vec<usize>> {
+        let mut cliques = vec![];
+        self.extend_clique(&mut vec![], self.computers.clone(), k, &mut cliques);
+        cliques
+    }
 
-    while let Some((name, mut path)) = queue.pop_front() {
-        if path.len() == 3 {
-            path.sort_unstable();
-            total.insert(path);
-            continue;
+    fn extend_clique(
+        &self,
+        clique: &mut Vec<usize>,
+        mut candidates: BitSet,
+        k: usize,
+        cliques: &mut Vec<Vec<usize>>,
+    ) {
+        if clique.len() == k {
+            cliques.push(clique.clone());
+            return;
         }
 
-        for next in adjacent[&name].iter() {
-            let mut temp = path.clone();
-            if !temp.contains(next) && adjacent[next].contains(&path[0]) {
-                temp.push(next.clone());
-                queue.push_back((next.clone(), temp));
-            }
+        for node in candidates.iter().collect_vec() {
+            clique.push(node);
+            self.extend_clique(
+                clique,
+                candidates.intersection(&self.adjacent[node]),
+                k,
+                cliques,
+            );
+            clique.pop();
+
+            // every clique with this node has now been found, so later nodes can skip it
+            candidates.remove(node);
         }
     }
 
-    Some(total.len())
-}
+    /// Every clique that no other computer could join, found by Bron–Kerbosch with Tomita
+    /// pivoting.
+    fn maximal_cliques(&self) -> Vec<Vec<usize>> {
+        let mut cliques = vec![];
+        self.bron_kerbosch(
+            &mut vec![],
+            self.computers.clone(),
+            BitSet::new(NAMES),
+            &mut cliques,
+        );
+        cliques
+    }
 
-pub fn part_two(input: &str) -> Option<String> {
-    let (adjacent, _) = parse(input);
+    fn bron_kerbosch(
+        &self,
+        clique: &mut Vec<usize>,
+        mut potential: BitSet,
+        mut processed: BitSet,
+        cliques: &mut Vec<Vec<usize>>,
+    ) {
+        // any maximal clique must contain the pivot or one of its non-neighbours, so choosing
+        // the pivot with the most potential neighbours leaves the fewest branches
+        let Some(pivot) = potential
+            .union(&processed)
+            .iter()
+            .max_by_key(|&node| potential.intersection_len(&self.adjacent[node]))
+        else {
+            cliques.push(clique.clone());
+            return;
+        };
+
+        for node in potential.difference(&self.adjacent[pivot]).iter() {
+            clique.push(node);
+            self.bron_kerbosch(
+                clique,
+                potential.intersection(&self.adjacent[node]),
+                processed.intersection(&self.adjacent[node]),
+                cliques,
+            );
+            clique.pop();
+
+            potential.remove(node);
+            processed.insert(node);
+        }
+    }
+}
 
-    let mut result = FxHashSet::default();
+pub fn part_one(input: &str) -> Option<usize> {
+    let network = Network::new(input);
+
+    Some(
+        network
+            .k_cliques(3)
+            .iter()
+            .filter(|clique| {
+                clique
+                    .iter()
+                    .any(|&node| node / 26 == (b't' - b'a') as usize)
+            })
+            .count(),
+    )
+}
 
-    bron_kerbosch(
-        Default::default(),
-        adjacent.keys().cloned().collect(),
-        Default::default(),
-        &adjacent,
-        &mut result,
-    );
+pub fn part_two(input: &str) -> Option<String> {
+    let network = Network::new(input);
+    let largest = network.maximal_cliques().into_iter().max_by_key(Vec::len)?;
 
-    Some(result.into_iter().sorted_unstable().join(","))
+    // ids sort in the same order as names
+    Some(largest.into_iter().sorted_unstable().map(name).join(","))
 }
 
 #[cfg(test)]
@@ -126,4 +167,89 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some("co,de,ka,ta".into()));
     }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(id("aa"), 0);
+        assert_eq!(id("zz"), NAMES - 1);
+        assert_eq!(name(id("ta")), "ta");
+    }
+
+    /// The cliques of every size up to four, then the maximal cliques, all as sorted ids.
+    type Cliques = (Vec<Vec<Vec<usize>>>, Vec<Vec<usize>>);
+
+    fn cliques(input: &str) -> Cliques {
+        let network = Network::new(input);
+        let mut maximal = network.maximal_cliques();
+        for clique in &mut maximal {
+            clique.sort_unstable();
+        }
+        maximal.sort();
+
+        let k_cliques = (1..=4).map(|k| network.k_cliques(k)).collect();
+        (k_cliques, maximal)
+    }
+
+    /// [`cliques`] by checking every subset of computers.
+    fn brute_force_cliques(input: &str) -> Cliques {
+        let network = Network::new(input);
+        let connected = |a: usize, b: usize| network.adjacent[a].contains(b);
+        let is_clique = |clique: &[usize]| {
+            clique
+                .iter()
+                .tuple_combinations()
+                .all(|(&a, &b)| connected(a, b))
+        };
+        let ids: Vec<usize> = network.computers.iter().collect();
+
+        let k_cliques = (1..=4)
+            .map(|k| {
+                ids.iter()
+                    .copied()
+                    .combinations(k)
+                    .filter(|clique| is_clique(clique))
+                    .collect()
+            })
+            .collect();
+        let maximal = ids
+            .iter()
+            .copied()
+            .powerset()
+            .filter(|clique| !clique.is_empty() && is_clique(clique))
+            .filter(|clique| {
+                ids.iter().all(|&other| {
+                    clique.contains(&other) || !clique.iter().all(|&node| connected(node, other))
+                })
+            })
+            .sorted()
+            .collect();
+        (k_cliques, maximal)
+    }
+
+    #[test]
+    fn test_cliques_on_random_networks() {
+        let generate = |rng: &mut advent_of_code::random::Rng| loop {
+            let nodes: Vec<String> = (0..12)
+                .map(|_| name(rng.below(NAMES as u64) as usize))
+                .unique()
+                .collect();
+            let edges: Vec<String> = nodes
+                .iter()
+                .tuple_combinations()
+                .filter(|_| rng.below(2) == 0)
+                .map(|(left, right)| format!("{left}-{right}"))
+                .collect();
+            if !edges.is_empty() {
+                return edges.join("\n");
+            }
+        };
+
+        advent_of_code::random::cross_check(
+            23,
+            20,
+            generate,
+            |input| cliques(input),
+            |input| brute_force_cliques(input),
+        );
+    }
 }
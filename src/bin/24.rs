@@ -1,12 +1,17 @@
 #![allow(clippy::upper_case_acronyms)]
 use std::{
-    collections::HashMap,
-    mem,
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Write},
+    fs, mem,
     ops::{BitAnd, BitOr, BitXor},
     rc::Rc,
 };
 
-use fxhash::{FxHashMap, FxHashSet};
+use advent_of_code::{
+    random::Rng,
+    template::{flag_value, has_flag},
+};
+use fxhash::FxHashMap;
 
 advent_of_code::solution!(24);
 
@@ -87,6 +92,16 @@ enum GateType {
     XOR,
 }
 
+impl GateType {
+    fn colour(self) -> &'static str {
+        match self {
+            GateType::AND => "lightblue",
+            GateType::OR => "palegreen",
+            GateType::XOR => "khaki",
+        }
+    }
+}
+
 impl From<&str> for GateType {
     fn from(value: &str) -> Self {
         match value {
@@ -117,6 +132,88 @@ impl Gate {
     }
 }
 
+/// The structural rules of a ripple-carry adder that [`Device::find_swapped_wires`] checks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Heuristic {
+    ZNotFromXor,
+    ZAsInput,
+    XorNotAdder,
+    XorFanOut,
+    AndFanOut,
+}
+
+impl Display for Heuristic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Heuristic::ZNotFromXor => write!(f, "z output not driven by an XOR gate"),
+            Heuristic::ZAsInput => write!(f, "z wire used as a gate input"),
+            Heuristic::XorNotAdder => {
+                write!(f, "XOR of internal wires does not drive a z output")
+            }
+            Heuristic::XorFanOut => write!(f, "XOR output does not feed exactly two gates"),
+            Heuristic::AndFanOut => write!(f, "AND output does not feed exactly one gate"),
+        }
+    }
+}
+
+/// The verified fix for a device: which wires were flagged and how they pair up.
+#[derive(Debug)]
+struct Repair {
+    flagged: BTreeMap<Rc<str>, Vec<Heuristic>>,
+    pairs: Vec<(Rc<str>, Rc<str>)>,
+    checks: usize,
+}
+
+impl Repair {
+    fn wires(&self) -> Vec<Rc<str>> {
+        let mut wires: Vec<_> = self
+            .pairs
+            .iter()
+            .flat_map(|(first, second)| [first.clone(), second.clone()])
+            .collect();
+        wires.sort_unstable();
+        wires
+    }
+}
+
+impl Display for Repair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Flagged wires:")?;
+        for (wire, heuristics) in &self.flagged {
+            let reasons = heuristics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            writeln!(f, "  {wire}: {}", reasons.join("; "))?;
+        }
+        writeln!(f, "Swapped pairs, verified on {} additions:", self.checks)?;
+        for (first, second) in &self.pairs {
+            writeln!(f, "  {first} <-> {second}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a device couldn't be repaired.
+#[derive(Debug)]
+enum RepairError {
+    OddFlagCount(usize),
+    NoValidPairing,
+}
+
+impl Display for RepairError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepairError::OddFlagCount(count) => {
+                write!(f, "{count} wires flagged, which can't form swap pairs")
+            }
+            RepairError::NoValidPairing => {
+                write!(f, "no pairing of the flagged wires makes the device add")
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Device {
     wires: FxHashMap<Rc<str>, Wire>,
@@ -170,19 +267,26 @@ impl Device {
         }
     }
 
-    fn evaluate_gates(&mut self) {
+    /// Propagates values until every z wire is set, returning `false` if some never are, e.g.
+    /// because swapped wires created a loop.
+    fn evaluate_gates(&mut self) -> bool {
         // take ownership for duration of function
         let gates = mem::take(&mut self.gates);
-        while self.get_outputs().contains(&None) {
+        let mut progress = true;
+        while progress && self.get_outputs().contains(&None) {
+            progress = false;
             for gate in &gates {
-                self.evaluate(gate);
+                progress |= self.evaluate(gate);
             }
         }
         // give it back
         self.gates = gates;
+
+        !self.get_outputs().contains(&None)
     }
 
-    fn evaluate(&mut self, gate: &Gate) {
+    /// Returns whether the gate's output got set.
+    fn evaluate(&mut self, gate: &Gate) -> bool {
         if self.wires[&gate.output].value.is_none() {
             let new_value = Some(
                 match (
@@ -193,14 +297,16 @@ impl Device {
                     (Some(state1), Some(state2), GateType::AND) => state1 & state2,
                     (Some(state1), Some(state2), GateType::OR) => state1 | state2,
                     (Some(state1), Some(state2), GateType::XOR) => state1 ^ state2,
-                    _ => return,
+                    _ => return false,
                 },
             );
 
             self.wires
                 .entry(gate.output.clone())
                 .and_modify(|wire| wire.value = new_value);
+            return true;
         }
+        false
     }
 
     fn get_outputs(&self) -> Vec<Option<State>> {
@@ -228,7 +334,7 @@ impl Device {
         value
     }
 
-    fn find_swapped_wires(&self) -> Vec<Rc<str>> {
+    fn find_swapped_wires(&self) -> BTreeMap<Rc<str>, Vec<Heuristic>> {
         let mut edges: HashMap<Rc<str>, Vec<Rc<str>>, _> = FxHashMap::default();
         for gate in &self.gates {
             edges
@@ -240,8 +346,15 @@ impl Device {
                 .or_default()
                 .push(gate.output.clone());
         }
+        let fan_out = |wire: &Rc<str>| edges.get(wire).map_or(0, Vec::len);
 
-        let mut broken = FxHashSet::default();
+        let mut broken: BTreeMap<Rc<str>, Vec<Heuristic>> = BTreeMap::new();
+        let mut flag = |wire: &Rc<str>, heuristic| {
+            let heuristics = broken.entry(wire.clone()).or_default();
+            if !heuristics.contains(&heuristic) {
+                heuristics.push(heuristic);
+            }
+        };
 
         let final_label: Rc<str> = format! {"z{}", self.z_outputs.len() -1}.into();
         for gate in &self.gates {
@@ -250,16 +363,16 @@ impl Device {
                 && gate.output != final_label
                 && gate.operation != GateType::XOR
             {
-                broken.insert(gate.output.clone());
+                flag(&gate.output, Heuristic::ZNotFromXor);
             }
 
             // z wires must be outputs only, never an input
             if gate.input1.starts_with('z') {
-                broken.insert(gate.input1.clone());
+                flag(&gate.input1, Heuristic::ZAsInput);
             }
 
             if gate.input2.starts_with('z') {
-                broken.insert(gate.input2.clone());
+                flag(&gate.input2, Heuristic::ZAsInput);
             }
 
             // XOR gates are only connected to x or y inputs and a z output
@@ -268,29 +381,339 @@ impl Device {
                 && !((gate.input1.starts_with('x') && gate.input2.starts_with('y'))
                     || (gate.input1.starts_with('y') && gate.input2.starts_with('x')))
             {
-                broken.insert(gate.output.clone());
+                flag(&gate.output, Heuristic::XorNotAdder);
             }
 
             // non z wires from XOR gates msut input to two other gates
             if gate.operation == GateType::XOR
                 && !gate.output.starts_with('z')
-                && edges[&gate.output].len() != 2
+                && fan_out(&gate.output) != 2
             {
-                broken.insert(gate.output.clone());
+                flag(&gate.output, Heuristic::XorFanOut);
             }
 
             // AND gates always output to only 1 node, save for the first addr
             if gate.operation == GateType::AND
                 && !gate.output.starts_with('z')
-                && edges[&gate.output].len() != 1
+                && fan_out(&gate.output) != 1
                 && !((*gate.input1 == *"x00" && *gate.input2 == *"y00")
                     || (*gate.input1 == *"y00" && *gate.input2 == *"x00"))
             {
-                broken.insert(gate.output.clone());
+                flag(&gate.output, Heuristic::AndFanOut);
+            }
+        }
+
+        broken
+    }
+
+    /// The adder bit a wire belongs to: its own number for x, y and z wires, otherwise the highest
+    /// input bit feeding into it.
+    fn positions(&self) -> FxHashMap<Rc<str>, usize> {
+        fn position(
+            wire: &Rc<str>,
+            drivers: &FxHashMap<Rc<str>, &Gate>,
+            positions: &mut FxHashMap<Rc<str>, usize>,
+        ) -> usize {
+            if let Some(&known) = positions.get(wire) {
+                return known;
+            }
+            // guards against loops through swapped wires
+            positions.insert(wire.clone(), 0);
+
+            let known = match drivers.get(wire) {
+                Some(gate) if !wire.starts_with('z') => position(&gate.input1, drivers, positions)
+                    .max(position(&gate.input2, drivers, positions)),
+                _ => wire[1..].parse().unwrap_or(0),
+            };
+            positions.insert(wire.clone(), known);
+            known
+        }
+
+        let drivers = self
+            .gates
+            .iter()
+            .map(|gate| (gate.output.clone(), gate))
+            .collect();
+        let mut positions = FxHashMap::default();
+        for wire in self.wires.keys() {
+            position(wire, &drivers, &mut positions);
+        }
+        positions
+    }
+
+    /// Renders the circuit as a Graphviz digraph. Gates are coloured by type and grouped into one
+    /// cluster per adder bit, with the bits ranked in order from left to right. Wires flagged by
+    /// [`Device::find_swapped_wires`] are drawn red.
+    fn to_dot(&self) -> String {
+        let suspects = self.find_swapped_wires();
+        let positions = self.positions();
+        let drivers: FxHashMap<_, _> = self
+            .gates
+            .iter()
+            .enumerate()
+            .map(|(index, gate)| (gate.output.clone(), index))
+            .collect();
+        let source = |wire: &Rc<str>| match drivers.get(wire) {
+            Some(index) if !wire.starts_with('z') => format!("g{index}"),
+            _ => format!("\"{wire}\""),
+        };
+        let highlight = |wire: &Rc<str>| {
+            if suspects.contains_key(wire) {
+                ", color=red, fontcolor=red, penwidth=2"
+            } else {
+                ""
+            }
+        };
+
+        let mut clusters: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for wire in self.wires.keys() {
+            if wire.starts_with(['x', 'y', 'z']) {
+                clusters
+                    .entry(positions[wire])
+                    .or_default()
+                    .push(format!("\"{wire}\" [shape=box{}];", highlight(wire)));
+            }
+        }
+        for (index, gate) in self.gates.iter().enumerate() {
+            clusters
+                .entry(positions[&gate.output])
+                .or_default()
+                .push(format!(
+                    "g{index} [label=\"{:?}\", style=filled, fillcolor={}{}];",
+                    gate.operation,
+                    gate.operation.colour(),
+                    highlight(&gate.output)
+                ));
+        }
+
+        let mut dot = String::from("digraph circuit {\n    rankdir=LR;\n");
+        for (bit, nodes) in clusters {
+            writeln!(dot, "    subgraph cluster_bit{bit:02} {{").unwrap();
+            writeln!(dot, "        label=\"bit {bit}\";").unwrap();
+            for node in nodes {
+                writeln!(dot, "        {node}").unwrap();
+            }
+            writeln!(dot, "    }}").unwrap();
+        }
+        for (index, gate) in self.gates.iter().enumerate() {
+            for input in [&gate.input1, &gate.input2] {
+                writeln!(
+                    dot,
+                    "    {} -> g{index} [label=\"{input}\"{}];",
+                    source(input),
+                    highlight(input)
+                )
+                .unwrap();
+            }
+            if gate.output.starts_with('z') {
+                writeln!(dot, "    g{index} -> \"{}\";", gate.output).unwrap();
+            }
+        }
+        // clusters only group nodes, so each bit's inputs share a rank and hang off the previous
+        // bit's output to lay the adder out in bit order
+        for bit in 0..self.input_bits() {
+            writeln!(dot, "    {{rank=same; \"x{bit:02}\"; \"y{bit:02}\";}}").unwrap();
+            if bit > 0 {
+                writeln!(
+                    dot,
+                    "    \"z{:02}\" -> \"x{bit:02}\" [style=invis];",
+                    bit - 1
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Number of bits in each of the x and y inputs.
+    fn input_bits(&self) -> usize {
+        self.z_outputs.len() - 1
+    }
+
+    /// Pairs of inputs that exercise every bit and the longest carry chains, followed by
+    /// random ones.
+    fn test_cases(&self, random: usize) -> Vec<(u64, u64)> {
+        let bits = self.input_bits();
+        let max = (1 << bits) - 1;
+        let mut cases = vec![(max, 1), (1, max), (max, max), (0, 0), (max, 0), (0, max)];
+        for bit in 0..bits {
+            cases.extend([(1 << bit, 1 << bit), (1 << bit, 0), (0, 1 << bit)]);
+        }
+
+        let mut rng = Rng::new(24);
+        cases.extend((0..random).map(|_| (rng.below(max + 1), rng.below(max + 1))));
+        cases
+    }
+
+    /// The gates in an order where every gate comes after the ones driving its inputs, or `None`
+    /// if swapped wires created a loop.
+    fn dependency_order(&self) -> Option<Vec<&Gate>> {
+        let drivers: FxHashMap<&str, usize> = self
+            .gates
+            .iter()
+            .enumerate()
+            .map(|(index, gate)| (&*gate.output, index))
+            .collect();
+        let mut waiting = vec![0; self.gates.len()];
+        let mut feeds = vec![vec![]; self.gates.len()];
+        for (index, gate) in self.gates.iter().enumerate() {
+            for input in [&gate.input1, &gate.input2] {
+                if let Some(&driver) = drivers.get(&**input) {
+                    waiting[index] += 1;
+                    feeds[driver].push(index);
+                }
+            }
+        }
+
+        let mut ready: Vec<usize> = (0..self.gates.len())
+            .filter(|&index| waiting[index] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(self.gates.len());
+        while let Some(index) = ready.pop() {
+            order.push(&self.gates[index]);
+            for &fed in &feeds[index] {
+                waiting[fed] -= 1;
+                if waiting[fed] == 0 {
+                    ready.push(fed);
+                }
+            }
+        }
+        (order.len() == self.gates.len()).then_some(order)
+    }
+
+    /// Whether the device computes `x + y` for every case. Runs 64 cases at a time, one bit of
+    /// each wire's `u64` per case, through the gates in dependency order.
+    fn adds_correctly(&self, cases: &[(u64, u64)]) -> bool {
+        let Some(order) = self.dependency_order() else {
+            return false;
+        };
+        let ids: FxHashMap<&str, usize> = self
+            .wires
+            .keys()
+            .enumerate()
+            .map(|(id, label)| (&**label, id))
+            .collect();
+        let gates: Vec<_> = order
+            .iter()
+            .map(|gate| {
+                (
+                    ids[&*gate.input1],
+                    ids[&*gate.input2],
+                    ids[&*gate.output],
+                    gate.operation,
+                )
+            })
+            .collect();
+        let inputs: Vec<_> = ids
+            .iter()
+            .filter(|(label, _)| label.starts_with(['x', 'y']))
+            .map(|(label, &id)| {
+                (
+                    label.starts_with('x'),
+                    label[1..].parse::<u32>().unwrap(),
+                    id,
+                )
+            })
+            .collect();
+        let outputs: Vec<_> = self.z_outputs.iter().map(|z| ids[&**z]).collect();
+
+        let mut values = vec![0u64; ids.len()];
+        cases.chunks(64).all(|chunk| {
+            values.fill(0);
+            for (lane, &(x, y)) in chunk.iter().enumerate() {
+                for &(is_x, bit, id) in &inputs {
+                    let operand = if is_x { x } else { y };
+                    values[id] |= (operand >> bit & 1) << lane;
+                }
+            }
+            for &(input1, input2, output, operation) in &gates {
+                values[output] = match operation {
+                    GateType::AND => values[input1] & values[input2],
+                    GateType::OR => values[input1] | values[input2],
+                    GateType::XOR => values[input1] ^ values[input2],
+                };
+            }
+
+            chunk.iter().enumerate().all(|(lane, &(x, y))| {
+                let sum = outputs
+                    .iter()
+                    .enumerate()
+                    .fold(0, |sum, (bit, &id)| sum | (values[id] >> lane & 1) << bit);
+                sum == x + y
+            })
+        })
+    }
+
+    fn swap_outputs(&mut self, first: &Rc<str>, second: &Rc<str>) {
+        for gate in &mut self.gates {
+            if gate.output == *first {
+                gate.output = second.clone();
+            } else if gate.output == *second {
+                gate.output = first.clone();
             }
         }
+    }
+
+    /// Tries the ways of pairing up the `unpaired` wires that `allowed` accepts, keeping the first
+    /// set of swaps that passes all `cases`.
+    fn pair_up(
+        &mut self,
+        unpaired: &mut Vec<Rc<str>>,
+        pairs: &mut Vec<(Rc<str>, Rc<str>)>,
+        cases: &[(u64, u64)],
+        allowed: &impl Fn(&Rc<str>, &Rc<str>) -> bool,
+    ) -> bool {
+        let Some(first) = unpaired.pop() else {
+            return self.adds_correctly(cases);
+        };
+
+        for index in 0..unpaired.len() {
+            if !allowed(&first, &unpaired[index]) {
+                continue;
+            }
+            let second = unpaired.remove(index);
+            self.swap_outputs(&first, &second);
+            pairs.push((first.clone(), second.clone()));
+
+            if self.pair_up(unpaired, pairs, cases, allowed) {
+                return true;
+            }
+
+            pairs.pop();
+            self.swap_outputs(&first, &second);
+            unpaired.insert(index, second);
+        }
+
+        unpaired.push(first);
+        false
+    }
+
+    /// Swaps the flagged wires back into place and proves the device now adds.
+    fn repair(&mut self) -> Result<Repair, RepairError> {
+        let flagged = self.find_swapped_wires();
+        if !flagged.len().is_multiple_of(2) {
+            return Err(RepairError::OddFlagCount(flagged.len()));
+        }
+
+        let cases = self.test_cases(64);
+        let mut unpaired: Vec<_> = flagged.keys().cloned().collect();
+        let mut pairs = Vec::new();
+        // a swap normally stays inside one full adder, so only wires of the same bit are paired
+        // unless that fails
+        let positions = self.positions();
+        let same_bit = |first: &Rc<str>, second: &Rc<str>| positions[first] == positions[second];
+        if !self.pair_up(&mut unpaired, &mut pairs, &cases, &same_bit)
+            && !self.pair_up(&mut unpaired, &mut pairs, &cases, &|_, _| true)
+        {
+            return Err(RepairError::NoValidPairing);
+        }
 
-        broken.into_iter().collect()
+        Ok(Repair {
+            flagged,
+            pairs,
+            checks: cases.len(),
+        })
     }
 }
 
@@ -303,13 +726,22 @@ pub fn part_one(input: &str) -> Option<u64> {
 }
 
 pub fn part_two(input: &str) -> Option<String> {
-    let device = Device::new(input);
+    let mut device = Device::new(input);
+
+    if let Some(path) = flag_value("--dot") {
+        fs::write(&path, device.to_dot()).expect("could not write DOT file");
+    }
 
-    let mut broken = device.find_swapped_wires();
+    let repair = device
+        .repair()
+        .map_err(|error| eprintln!("Cannot repair device: {error}"))
+        .ok()?;
 
-    broken.sort_unstable();
+    if has_flag("--report") {
+        print!("{repair}");
+    }
 
-    Some(broken.join(","))
+    Some(repair.wires().join(","))
 }
 
 #[cfg(test)]
@@ -321,4 +753,145 @@ mod tests {
         let result = part_one(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(2024));
     }
+
+    /// A ripple-carry adder over `bits` bits with the outputs of each pair in `swaps` exchanged.
+    fn ripple_carry_adder(bits: usize, swaps: &[(&str, &str)]) -> String {
+        let mut gates = vec![
+            ("x00", "XOR", "y00", "z00".to_string()),
+            ("x00", "AND", "y00", "c00".to_string()),
+        ];
+        let mut inputs = vec![];
+        for bit in 0..bits {
+            inputs.push(format!("x{bit:02}: 0\ny{bit:02}: 0"));
+        }
+
+        let names: Vec<_> = (0..bits)
+            .map(|bit| {
+                let carry = if bit == bits - 1 {
+                    format!("z{bits:02}")
+                } else {
+                    format!("c{bit:02}")
+                };
+                (
+                    format!("x{bit:02}"),
+                    format!("y{bit:02}"),
+                    format!("t{bit:02}"),
+                    format!("a{bit:02}"),
+                    format!("b{bit:02}"),
+                    carry,
+                )
+            })
+            .collect();
+        for bit in 1..bits {
+            let (x, y, t, a, b, carry) = &names[bit];
+            let previous = &names[bit - 1].5;
+            gates.extend([
+                (x.as_str(), "XOR", y.as_str(), t.clone()),
+                (t, "XOR", previous, format!("z{bit:02}")),
+                (x, "AND", y, a.clone()),
+                (t, "AND", previous, b.clone()),
+                (a, "OR", b, carry.clone()),
+            ]);
+        }
+
+        let gates = gates
+            .into_iter()
+            .map(|(input1, operation, input2, mut output)| {
+                for &(first, second) in swaps {
+                    if output == first {
+                        output = second.to_string();
+                    } else if output == second {
+                        output = first.to_string();
+                    }
+                }
+                format!("{input1} {operation} {input2} -> {output}")
+            })
+            .collect::<Vec<_>>();
+
+        format!("{}\n\n{}", inputs.join("\n"), gates.join("\n"))
+    }
+
+    #[test]
+    fn test_intact_adder_adds() {
+        let device = Device::new(&ripple_carry_adder(12, &[]));
+        assert!(device.find_swapped_wires().is_empty());
+        let cases = device.test_cases(32);
+        assert!(device.adds_correctly(&cases));
+    }
+
+    #[test]
+    fn test_repair_pairs_swaps() {
+        let swaps = [
+            ("z03", "c03"),
+            ("t05", "a05"),
+            ("z07", "b07"),
+            ("z09", "a09"),
+        ];
+        let mut device = Device::new(&ripple_carry_adder(12, &swaps));
+        assert!(!device.adds_correctly(&device.test_cases(0)));
+
+        let repair = device.repair().unwrap();
+        let mut pairs: Vec<_> = repair
+            .pairs
+            .iter()
+            .map(|(first, second)| {
+                let mut pair = [first.to_string(), second.to_string()];
+                pair.sort();
+                pair
+            })
+            .collect();
+        pairs.sort();
+        assert_eq!(
+            pairs,
+            [
+                ["a05", "t05"],
+                ["a09", "z09"],
+                ["b07", "z07"],
+                ["c03", "z03"]
+            ]
+        );
+        assert_eq!(repair.flagged[&Rc::from("a05")], [Heuristic::XorFanOut]);
+        assert_eq!(repair.flagged[&Rc::from("t05")], [Heuristic::AndFanOut]);
+        assert!(device.adds_correctly(&device.test_cases(32)));
+
+        let result = part_two(&ripple_carry_adder(12, &swaps));
+        assert_eq!(result, Some("a05,a09,b07,c03,t05,z03,z07,z09".into()));
+    }
+
+    #[test]
+    fn test_repair_across_bits() {
+        let mut device = Device::new(&ripple_carry_adder(12, &[("t05", "a03"), ("z08", "c08")]));
+        let positions = device.positions();
+        assert_ne!(positions[&Rc::from("t05")], positions[&Rc::from("a03")]);
+
+        let repair = device.repair().unwrap();
+        assert_eq!(repair.wires().join(","), "a03,c08,t05,z08");
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let device = Device::new(&ripple_carry_adder(4, &[("z02", "c02")]));
+        let dot = device.to_dot();
+
+        assert!(dot.starts_with("digraph circuit {"));
+        assert_eq!(dot.matches("subgraph cluster_bit").count(), 5);
+        assert_eq!(dot.matches("[label=\"XOR\", style=filled").count(), 7);
+        assert!(dot.contains("\"z02\" [shape=box, color=red"));
+        assert!(dot.contains("\"x00\" -> g0 [label=\"x00\"];"));
+        assert!(dot.contains("{rank=same; \"x03\"; \"y03\";}"));
+        assert!(dot.contains("\"z02\" -> \"x03\" [style=invis];"));
+        assert!(!dot.contains("\"x04\""));
+
+        let positions = device.positions();
+        assert_eq!(positions[&Rc::from("t03")], 3);
+        assert_eq!(positions[&Rc::from("c01")], 1);
+        assert_eq!(positions[&Rc::from("z04")], 4);
+    }
+
+    #[test]
+    fn test_repair_rejects_unpairable() {
+        let mut device = Device::new(&ripple_carry_adder(12, &[("z03", "c03"), ("t05", "a05")]));
+        device.gates.retain(|gate| *gate.output != *"b07");
+        assert!(device.repair().is_err());
+    }
 }
@@ -0,0 +1,117 @@
+//! A multiset for count-compressed simulations, where many identical items evolve the same way
+//! and only how often each one occurs matters.
+
+use std::hash::Hash;
+
+use fxhash::FxHashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counter<T: Hash + Eq> {
+    counts: FxHashMap<T, u64>,
+}
+
+impl<T: Hash + Eq> Default for Counter<T> {
+    fn default() -> Self {
+        Self {
+            counts: FxHashMap::default(),
+        }
+    }
+}
+
+impl<T: Hash + Eq> Counter<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `count` copies of `item`.
+    pub fn add(&mut self, item: T, count: u64) {
+        *self.counts.entry(item).or_insert(0) += count;
+    }
+
+    /// How many copies of `item` there are.
+    pub fn get(&self, item: &T) -> u64 {
+        self.counts.get(item).copied().unwrap_or(0)
+    }
+
+    /// Number of distinct items.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Number of items, counting duplicates.
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&T, u64)> {
+        self.counts.iter().map(|(item, &count)| (item, count))
+    }
+
+    /// Replaces every item by the items `f` turns it into, keeping the multiplicities.
+    /// This is one step of a count-compressed simulation.
+    pub fn flat_map<I: IntoIterator<Item = T>>(&self, mut f: impl FnMut(&T) -> I) -> Self {
+        let mut next = Self::default();
+        for (item, &count) in &self.counts {
+            for produced in f(item) {
+                next.add(produced, count);
+            }
+        }
+        next
+    }
+}
+
+impl<T: Hash + Eq> FromIterator<T> for Counter<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut counter = Self::default();
+        counter.extend(iter.into_iter().map(|item| (item, 1)));
+        counter
+    }
+}
+
+impl<T: Hash + Eq> Extend<(T, u64)> for Counter<T> {
+    fn extend<I: IntoIterator<Item = (T, u64)>>(&mut self, iter: I) {
+        for (item, count) in iter {
+            self.add(item, count);
+        }
+    }
+}
+
+impl<T: Hash + Eq> IntoIterator for Counter<T> {
+    type Item = (T, u64);
+    type IntoIter = std::collections::hash_map::IntoIter<T, u64>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.counts.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_duplicates() {
+        let counter: Counter<char> = "abracadabra".chars().collect();
+        assert_eq!(counter.get(&'a'), 5);
+        assert_eq!(counter.get(&'z'), 0);
+        assert_eq!(counter.len(), 5);
+        assert_eq!(counter.total(), 11);
+    }
+
+    #[test]
+    fn flat_map_keeps_multiplicities() {
+        let counter: Counter<u32> = [1, 1, 2].into_iter().collect();
+        let doubled = counter.flat_map(|&n| [n, n * 10]);
+        assert_eq!(doubled.get(&1), 2);
+        assert_eq!(doubled.get(&10), 2);
+        assert_eq!(doubled.get(&20), 1);
+        assert_eq!(doubled.total(), 6);
+
+        let emptied = doubled.flat_map(|_| None);
+        assert!(emptied.is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+//! Cycle detection over state-transition functions.
+//!
+//! All helpers take an initial state and a `step` function producing the next state, and assume
+//! the sequence of states eventually repeats.
+
+use std::hash::Hash;
+
+use fxhash::FxHashMap;
+
+/// The shape of an eventually periodic sequence: `start` steps lead into a loop of `length` states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub length: usize,
+}
+
+impl Cycle {
+    /// Maps step `n` onto the earliest step that reaches the same state.
+    pub fn reduce(&self, n: usize) -> usize {
+        if n < self.start {
+            n
+        } else {
+            self.start + (n - self.start) % self.length
+        }
+    }
+}
+
+/// Floyd's tortoise and hare. Keeps two states alive and calls `step` roughly three times per
+/// state in the sequence.
+pub fn floyd<T: PartialEq + Clone>(initial: T, mut step: impl FnMut(&T) -> T) -> Cycle {
+    let mut tortoise = step(&initial);
+    let mut hare = step(&tortoise);
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        let midway = step(&hare);
+        hare = step(&midway);
+    }
+
+    let mut start = 0;
+    tortoise = initial;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    let mut length = 1;
+    hare = step(&tortoise);
+    while tortoise != hare {
+        hare = step(&hare);
+        length += 1;
+    }
+
+    Cycle { start, length }
+}
+
+/// Brent's algorithm. Same result as [`floyd`] with fewer calls to `step`.
+pub fn brent<T: PartialEq + Clone>(initial: T, mut step: impl FnMut(&T) -> T) -> Cycle {
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(&initial);
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = step(&hare);
+        length += 1;
+    }
+
+    tortoise = initial.clone();
+    hare = initial;
+    for _ in 0..length {
+        hare = step(&hare);
+    }
+
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    Cycle { start, length }
+}
+
+/// Returns the state after `steps` applications of `step`, simulating only until the first
+/// repeated state and extrapolating the rest of the way through the loop.
+pub fn state_at<T: Hash + Eq + Clone>(
+    initial: T,
+    steps: usize,
+    mut step: impl FnMut(&T) -> T,
+) -> T {
+    let mut seen = FxHashMap::default();
+    let mut history = vec![initial];
+
+    for current in 0..steps {
+        let state = &history[current];
+        if let Some(&start) = seen.get(state) {
+            let cycle = Cycle {
+                start,
+                length: current - start,
+            };
+            return history.swap_remove(cycle.reduce(steps));
+        }
+        seen.insert(state.clone(), current);
+
+        let next = step(state);
+        history.push(next);
+    }
+
+    history.swap_remove(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0 -> 1 -> 2 -> 3 -> 4 -> 5 -> 2 -> ...
+    fn rho(state: &u32) -> u32 {
+        if *state < 5 {
+            state + 1
+        } else {
+            2
+        }
+    }
+
+    #[test]
+    fn floyd_finds_cycle() {
+        assert_eq!(
+            floyd(0, rho),
+            Cycle {
+                start: 2,
+                length: 4
+            }
+        );
+        assert_eq!(
+            floyd(3, rho),
+            Cycle {
+                start: 0,
+                length: 4
+            }
+        );
+    }
+
+    #[test]
+    fn brent_finds_cycle() {
+        assert_eq!(
+            brent(0, rho),
+            Cycle {
+                start: 2,
+                length: 4
+            }
+        );
+        assert_eq!(
+            brent(3, rho),
+            Cycle {
+                start: 0,
+                length: 4
+            }
+        );
+        assert_eq!(
+            brent(7, |_| 7),
+            Cycle {
+                start: 0,
+                length: 1
+            }
+        );
+    }
+
+    #[test]
+    fn cycle_reduces_steps() {
+        let cycle = Cycle {
+            start: 2,
+            length: 4,
+        };
+        assert_eq!(cycle.reduce(1), 1);
+        assert_eq!(cycle.reduce(6), 2);
+        assert_eq!(cycle.reduce(1_000_000_001), 5);
+    }
+
+    #[test]
+    fn state_at_extrapolates() {
+        for steps in 0..20 {
+            let mut expected = 0;
+            for _ in 0..steps {
+                expected = rho(&expected);
+            }
+            assert_eq!(state_at(0, steps, rho), expected);
+        }
+        assert_eq!(state_at(0, 1_000_000_000_000, rho), 4);
+    }
+}
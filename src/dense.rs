@@ -0,0 +1,389 @@
+//! Set and map types keyed by small dense integers, for hot loops where hashing dominates.
+
+/// A fixed-size set of indices in `0..capacity`, one bit each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitSet {
+    words: Vec<u64>,
+    capacity: usize,
+}
+
+impl BitSet {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            words: vec![0; capacity.div_ceil(64)],
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Adds `index`, returning whether it was newly inserted.
+    #[inline]
+    pub fn insert(&mut self, index: usize) -> bool {
+        debug_assert!(index < self.capacity, "index {index} out of bounds");
+        let (word, mask) = (index / 64, 1 << (index % 64));
+        let is_new = self.words[word] & mask == 0;
+        self.words[word] |= mask;
+        is_new
+    }
+
+    /// Removes `index`, returning whether it was present.
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> bool {
+        debug_assert!(index < self.capacity, "index {index} out of bounds");
+        let (word, mask) = (index / 64, 1 << (index % 64));
+        let was_present = self.words[word] & mask != 0;
+        self.words[word] &= !mask;
+        was_present
+    }
+
+    #[inline]
+    pub fn contains(&self, index: usize) -> bool {
+        debug_assert!(index < self.capacity, "index {index} out of bounds");
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    /// The indices in the set, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(index, &word)| {
+            let mut remaining = word;
+            std::iter::from_fn(move || {
+                if remaining == 0 {
+                    return None;
+                }
+                let bit = remaining.trailing_zeros() as usize;
+                remaining &= remaining - 1;
+                Some(index * 64 + bit)
+            })
+        })
+    }
+
+    /// The indices in both sets.
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a & b)
+    }
+
+    /// The indices in either set.
+    pub fn union(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a | b)
+    }
+
+    /// The indices in this set but not `other`.
+    pub fn difference(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a & !b)
+    }
+
+    /// The size of the intersection, without building it.
+    pub fn intersection_len(&self, other: &Self) -> usize {
+        debug_assert_eq!(self.capacity, other.capacity, "capacities differ");
+        self.words
+            .iter()
+            .zip(&other.words)
+            .map(|(a, b)| (a & b).count_ones() as usize)
+            .sum()
+    }
+
+    fn combine(&self, other: &Self, operation: impl Fn(u64, u64) -> u64) -> Self {
+        debug_assert_eq!(self.capacity, other.capacity, "capacities differ");
+        Self {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(&a, &b)| operation(a, b))
+                .collect(),
+            capacity: self.capacity,
+        }
+    }
+}
+
+/// A set of grid cells, optionally split into `layers` per cell (e.g. one per facing direction).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GridSet {
+    width: usize,
+    height: usize,
+    layers: usize,
+    bits: BitSet,
+}
+
+impl GridSet {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::with_layers(width, height, 1)
+    }
+
+    pub fn with_layers(width: usize, height: usize, layers: usize) -> Self {
+        Self {
+            width,
+            height,
+            layers,
+            bits: BitSet::new(width * height * layers),
+        }
+    }
+
+    #[inline]
+    fn index(&self, x: usize, y: usize, layer: usize) -> usize {
+        // a stray x would otherwise wrap silently onto the next row
+        assert!(
+            x < self.width && y < self.height && layer < self.layers,
+            "cell ({x}, {y}) layer {layer} out of bounds"
+        );
+        (y * self.width + x) * self.layers + layer
+    }
+
+    /// Adds the cell at `(x, y)`, returning whether it was newly inserted.
+    #[inline]
+    pub fn insert(&mut self, x: usize, y: usize) -> bool {
+        self.insert_layer(x, y, 0)
+    }
+
+    #[inline]
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        self.contains_layer(x, y, 0)
+    }
+
+    #[inline]
+    pub fn insert_layer(&mut self, x: usize, y: usize, layer: usize) -> bool {
+        let index = self.index(x, y, layer);
+        self.bits.insert(index)
+    }
+
+    #[inline]
+    pub fn contains_layer(&self, x: usize, y: usize, layer: usize) -> bool {
+        self.bits.contains(self.index(x, y, layer))
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.clear();
+    }
+
+    /// Number of `(x, y, layer)` entries in the set.
+    pub fn len(&self) -> usize {
+        self.bits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    /// The distinct cells with at least one layer set, in row-major order.
+    pub fn positions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let width = self.width;
+        let layers = self.layers;
+        let mut previous = None;
+        self.bits.iter().filter_map(move |index| {
+            let cell = index / layers;
+            if previous == Some(cell) {
+                return None;
+            }
+            previous = Some(cell);
+            Some((cell % width, cell / width))
+        })
+    }
+}
+
+/// A map keyed by small integers, backed by a vector indexed by key. Grows to fit the largest key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DenseMap<V> {
+    values: Vec<Option<V>>,
+    len: usize,
+}
+
+impl<V> Default for DenseMap<V> {
+    fn default() -> Self {
+        Self {
+            values: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<V> DenseMap<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut values = Vec::with_capacity(capacity);
+        values.resize_with(capacity, || None);
+        Self { values, len: 0 }
+    }
+
+    pub fn insert(&mut self, key: usize, value: V) -> Option<V> {
+        if key >= self.values.len() {
+            self.values.resize_with(key + 1, || None);
+        }
+        let previous = self.values[key].replace(value);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    pub fn remove(&mut self, key: usize) -> Option<V> {
+        let previous = self.values.get_mut(key)?.take();
+        if previous.is_some() {
+            self.len -= 1;
+        }
+        previous
+    }
+
+    #[inline]
+    pub fn get(&self, key: usize) -> Option<&V> {
+        self.values.get(key)?.as_ref()
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut V> {
+        self.values.get_mut(key)?.as_mut()
+    }
+
+    #[inline]
+    pub fn contains_key(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the value for `key`, inserting the result of `default` first if there is none.
+    pub fn get_or_insert_with(&mut self, key: usize, default: impl FnOnce() -> V) -> &mut V {
+        if !self.contains_key(key) {
+            self.insert(key, default());
+        }
+        self.values[key].as_mut().unwrap()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The entries in ascending key order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &V)> {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(key, value)| Some((key, value.as_ref()?)))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.values.iter().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bitset_insert_remove() {
+        let mut set = BitSet::new(130);
+        assert!(set.insert(0));
+        assert!(set.insert(64));
+        assert!(set.insert(129));
+        assert!(!set.insert(64));
+        assert!(set.contains(129));
+        assert!(!set.contains(128));
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.iter().collect::<Vec<_>>(), [0, 64, 129]);
+
+        assert!(set.remove(64));
+        assert!(!set.remove(64));
+        assert_eq!(set.len(), 2);
+
+        set.clear();
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn bitset_set_operations() {
+        let mut left = BitSet::new(130);
+        let mut right = BitSet::new(130);
+        for index in [1, 64, 100, 129] {
+            left.insert(index);
+        }
+        for index in [0, 64, 129] {
+            right.insert(index);
+        }
+
+        assert_eq!(
+            left.intersection(&right).iter().collect::<Vec<_>>(),
+            [64, 129]
+        );
+        assert_eq!(left.intersection_len(&right), 2);
+        assert_eq!(
+            left.union(&right).iter().collect::<Vec<_>>(),
+            [0, 1, 64, 100, 129]
+        );
+        assert_eq!(left.difference(&right).iter().collect::<Vec<_>>(), [1, 100]);
+    }
+
+    #[test]
+    fn gridset_layers() {
+        let mut set = GridSet::with_layers(3, 2, 4);
+        assert!(set.insert_layer(2, 1, 3));
+        assert!(set.insert_layer(2, 1, 0));
+        assert!(set.insert_layer(0, 1, 1));
+        assert!(!set.insert_layer(2, 1, 3));
+        assert!(set.contains_layer(2, 1, 0));
+        assert!(!set.contains_layer(2, 1, 1));
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.positions().collect::<Vec<_>>(), [(0, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn gridset_cells() {
+        let mut set = GridSet::new(5, 5);
+        assert!(set.insert(4, 4));
+        assert!(!set.insert(4, 4));
+        assert!(set.contains(4, 4));
+        assert!(!set.contains(3, 4));
+        assert_eq!(set.positions().collect::<Vec<_>>(), [(4, 4)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn gridset_rejects_cells_past_the_row() {
+        let mut set = GridSet::new(5, 5);
+        set.insert(5, 0);
+    }
+
+    #[test]
+    fn densemap_entries() {
+        let mut map = DenseMap::with_capacity(4);
+        assert_eq!(map.insert(2, "two"), None);
+        assert_eq!(map.insert(10, "ten"), None);
+        assert_eq!(map.insert(2, "deux"), Some("two"));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(2), Some(&"deux"));
+        assert_eq!(map.get(3), None);
+        assert_eq!(map.get(100), None);
+
+        *map.get_or_insert_with(3, || "three") = "trois";
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            [(2, &"deux"), (3, &"trois"), (10, &"ten")]
+        );
+
+        assert_eq!(map.remove(10), Some("ten"));
+        assert_eq!(map.remove(10), None);
+        assert_eq!(map.len(), 2);
+    }
+}
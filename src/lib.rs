@@ -1,3 +1,9 @@
+pub mod counter;
+pub mod cycle;
+pub mod dense;
+pub mod ocr;
+pub mod parse;
+pub mod random;
 pub mod template;
 
 // Use this file to add helper functions and additional modules.
@@ -27,6 +27,7 @@ mod args {
             release: bool,
             dhat: bool,
             submit: Option<u8>,
+            options: Vec<String>,
         },
         All {
             release: bool,
@@ -43,7 +44,7 @@ mod args {
     pub fn parse() -> Result<AppArguments, Box<dyn std::error::Error>> {
         let mut args = pico_args::Arguments::from_env();
 
-        let app_args = match args.subcommand()?.as_deref() {
+        let mut app_args = match args.subcommand()?.as_deref() {
             Some("all") => AppArguments::All {
                 release: args.contains("--release"),
             },
@@ -73,6 +74,7 @@ mod args {
                 release: args.contains("--release"),
                 submit: args.opt_value_from_str("--submit")?,
                 dhat: args.contains("--dhat"),
+                options: Vec::new(),
             },
             #[cfg(feature = "today")]
             Some("today") => AppArguments::Today,
@@ -87,7 +89,13 @@ mod args {
         };
 
         let remaining = args.finish();
-        if !remaining.is_empty() {
+        if let AppArguments::Solve { options, .. } = &mut app_args {
+            // anything left over is meant for the solution itself
+            *options = remaining
+                .into_iter()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect();
+        } else if !remaining.is_empty() {
             eprintln!("Warning: unknown argument(s): {remaining:?}.");
         }
 
@@ -121,7 +129,8 @@ fn main() {
                 release,
                 dhat,
                 submit,
-            } => solve::handle(day, release, dhat, submit),
+                options,
+            } => solve::handle(day, release, dhat, submit, &options),
             #[cfg(feature = "today")]
             AppArguments::Today => {
                 match Day::today() {
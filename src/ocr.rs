@@ -0,0 +1,277 @@
+//! Recognizes the block-letter fonts Advent of Code renders some answers in.
+//!
+//! Two fonts are known: letters 4 pixels wide and 6 tall on a 5 pixel pitch, and letters 6 pixels
+//! wide and 10 tall on an 8 pixel pitch. The font is picked from the height of the art.
+
+struct Font {
+    height: usize,
+    pitch: usize,
+    glyphs: &'static [(char, &'static [&'static str])],
+}
+
+const SMALL: Font = Font {
+    height: 6,
+    pitch: 5,
+    glyphs: &[
+        ('A', &[".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+        ('B', &["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+        ('C', &[".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+        ('E', &["####", "#...", "###.", "#...", "#...", "####"]),
+        ('F', &["####", "#...", "###.", "#...", "#...", "#..."]),
+        ('G', &[".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+        ('H', &["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+        ('I', &[".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+        ('J', &["..##", "...#", "...#", "...#", "#..#", ".##."]),
+        ('K', &["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+        ('L', &["#...", "#...", "#...", "#...", "#...", "####"]),
+        ('O', &[".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('P', &["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+        ('R', &["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+        ('S', &[".###", "#...", "#...", ".##.", "...#", "###."]),
+        ('U', &["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('Y', &["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+        ('Z', &["####", "...#", "..#.", ".#..", "#...", "####"]),
+    ],
+};
+
+const LARGE: Font = Font {
+    height: 10,
+    pitch: 8,
+    glyphs: &[
+        (
+            'A',
+            &[
+                "..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#",
+                "#....#", "#....#",
+            ],
+        ),
+        (
+            'B',
+            &[
+                "#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#",
+                "#....#", "#####.",
+            ],
+        ),
+        (
+            'C',
+            &[
+                ".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+                "#....#", ".####.",
+            ],
+        ),
+        (
+            'E',
+            &[
+                "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+                "#.....", "######",
+            ],
+        ),
+        (
+            'F',
+            &[
+                "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+                "#.....", "#.....",
+            ],
+        ),
+        (
+            'G',
+            &[
+                ".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#",
+                "#...##", ".###.#",
+            ],
+        ),
+        (
+            'H',
+            &[
+                "#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#",
+                "#....#", "#....#",
+            ],
+        ),
+        (
+            'J',
+            &[
+                "...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.",
+                "#...#.", ".###..",
+            ],
+        ),
+        (
+            'K',
+            &[
+                "#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..",
+                "#...#.", "#....#",
+            ],
+        ),
+        (
+            'L',
+            &[
+                "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+                "#.....", "######",
+            ],
+        ),
+        (
+            'N',
+            &[
+                "#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##",
+                "#...##", "#....#",
+            ],
+        ),
+        (
+            'P',
+            &[
+                "#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....",
+                "#.....", "#.....",
+            ],
+        ),
+        (
+            'R',
+            &[
+                "#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.",
+                "#....#", "#....#",
+            ],
+        ),
+        (
+            'X',
+            &[
+                "#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.",
+                "#....#", "#....#",
+            ],
+        ),
+        (
+            'Z',
+            &[
+                "######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....",
+                "#.....", "######",
+            ],
+        ),
+    ],
+};
+
+impl Font {
+    fn for_height(height: usize) -> Option<&'static Font> {
+        [&SMALL, &LARGE]
+            .into_iter()
+            .find(|font| font.height == height)
+    }
+
+    /// Matches the letter whose cell starts at column `left`.
+    fn letter<R: AsRef<[bool]>>(&self, rows: &[R], left: usize) -> Option<char> {
+        let lit = |x: usize, y: usize| rows[y].as_ref().get(x).copied().unwrap_or(false);
+
+        self.glyphs
+            .iter()
+            .find(|(_, glyph)| {
+                glyph.iter().enumerate().all(|(y, glyph_row)| {
+                    let glyph_row = glyph_row.as_bytes();
+                    (0..self.pitch).all(|dx| {
+                        let expected = glyph_row.get(dx) == Some(&b'#');
+                        lit(left + dx, y) == expected
+                    })
+                })
+            })
+            .map(|&(letter, _)| letter)
+    }
+}
+
+/// Reads the letters drawn by the lit pixels in `rows`. Blank rows around the art and blank
+/// columns to its left are ignored. Returns `None` if the height doesn't match a font or any
+/// letter is unknown.
+pub fn recognize<R: AsRef<[bool]>>(rows: &[R]) -> Option<String> {
+    let is_blank = |row: &R| !row.as_ref().contains(&true);
+    let top = rows.iter().position(|row| !is_blank(row))?;
+    let bottom = rows.iter().rposition(|row| !is_blank(row))?;
+    let rows = &rows[top..=bottom];
+
+    let font = Font::for_height(rows.len())?;
+
+    let left = rows
+        .iter()
+        .filter_map(|row| row.as_ref().iter().position(|&lit| lit))
+        .min()?;
+    let right = rows
+        .iter()
+        .filter_map(|row| row.as_ref().iter().rposition(|&lit| lit))
+        .max()?;
+
+    // letters like `J` start with blank columns, so the art may begin part way into the first cell
+    (0..font.pitch.min(left + 1)).find_map(|shift| {
+        (left - shift..=right)
+            .step_by(font.pitch)
+            .map(|x| font.letter(rows, x))
+            .collect()
+    })
+}
+
+/// Like [`recognize`], for art drawn with `#` or `█` as lit pixels.
+pub fn recognize_str(art: &str) -> Option<String> {
+    let rows: Vec<Vec<bool>> = art
+        .lines()
+        .map(|line| line.chars().map(|c| c == '#' || c == '█').collect())
+        .collect();
+    recognize(&rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(font: &Font, text: &str) -> String {
+        (0..font.height)
+            .map(|y| {
+                text.chars()
+                    .map(|letter| {
+                        let (_, glyph) = font.glyphs.iter().find(|(c, _)| *c == letter).unwrap();
+                        format!("{:.<width$}", glyph[y], width = font.pitch)
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn reads_small_font() {
+        let art = "\
+####.#..#.###..#..#.###..
+#....#.#..#..#.#..#.#..#.
+###..##...#..#.#..#.#..#.
+#....#.#..###..#..#.###..
+#....#.#..#.#..#..#.#....
+####.#..#.#..#..##..#....";
+        assert_eq!(recognize_str(art), Some("EKRUP".into()));
+    }
+
+    #[test]
+    fn reads_every_letter() {
+        for font in [&SMALL, &LARGE] {
+            let alphabet: String = font.glyphs.iter().map(|(letter, _)| letter).collect();
+            let art = render(font, &alphabet);
+            assert_eq!(recognize_str(&art), Some(alphabet));
+        }
+    }
+
+    #[test]
+    fn ignores_margins() {
+        let art = format!("\n{}\n\n", render(&LARGE, "HXN"))
+            .lines()
+            .map(|line| format!("   {}", line.replace('#', "█").replace('.', " ")))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(recognize_str(&art), Some("HXN".into()));
+    }
+
+    #[test]
+    fn aligns_on_leading_blank_columns() {
+        assert_eq!(recognize_str(&render(&SMALL, "JIZ")), Some("JIZ".into()));
+        assert_eq!(recognize_str(&render(&LARGE, "JAZ")), Some("JAZ".into()));
+    }
+
+    #[test]
+    fn rejects_unknown_art() {
+        assert_eq!(recognize_str("123"), None);
+        assert_eq!(recognize_str("#\n#\n#"), None);
+        assert_eq!(
+            recognize_str(&render(&SMALL, "AB").replace("###.", "#.#.")),
+            None
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Zero-allocation helpers for pulling numbers and sections out of puzzle inputs.
+//!
+//! The integer scanners work directly on bytes and skip anything that isn't a digit, which covers
+//! most inputs regardless of the punctuation around the numbers (`p=0,4 v=3,-3`,
+//! `Button A: X+94, Y+34`, `3   4`, ...).
+
+use std::marker::PhantomData;
+
+/// Integer types that can be built up one decimal digit at a time.
+pub trait Integer: Copy {
+    const ZERO: Self;
+    /// Whether a `-` directly in front of the digits negates the value.
+    const SIGNED: bool;
+
+    fn push_digit(self, digit: u8) -> Self;
+    fn negate(self) -> Self;
+}
+
+macro_rules! impl_unsigned {
+    ($($ty:ty),*) => {$(
+        impl Integer for $ty {
+            const ZERO: Self = 0;
+            const SIGNED: bool = false;
+
+            #[inline]
+            fn push_digit(self, digit: u8) -> Self {
+                self * 10 + digit as $ty
+            }
+
+            #[inline]
+            fn negate(self) -> Self {
+                self
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_signed {
+    ($($ty:ty),*) => {$(
+        impl Integer for $ty {
+            const ZERO: Self = 0;
+            const SIGNED: bool = true;
+
+            #[inline]
+            fn push_digit(self, digit: u8) -> Self {
+                self * 10 + digit as $ty
+            }
+
+            #[inline]
+            fn negate(self) -> Self {
+                -self
+            }
+        }
+    )*};
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128, usize);
+impl_signed!(i8, i16, i32, i64, i128, isize);
+
+/// Iterator over every integer in a byte slice, see [`numbers`].
+#[derive(Debug, Clone)]
+pub struct Numbers<'a, T> {
+    bytes: &'a [u8],
+    position: usize,
+    _integer: PhantomData<T>,
+}
+
+impl<T: Integer> Iterator for Numbers<'_, T> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = self.bytes;
+        let mut position = self.position;
+
+        while position < bytes.len() && !bytes[position].is_ascii_digit() {
+            position += 1;
+        }
+        if position == bytes.len() {
+            self.position = position;
+            return None;
+        }
+
+        let is_negative = T::SIGNED && position > 0 && bytes[position - 1] == b'-';
+
+        let mut value = T::ZERO;
+        while position < bytes.len() && bytes[position].is_ascii_digit() {
+            value = value.push_digit(bytes[position] - b'0');
+            position += 1;
+        }
+        self.position = position;
+
+        Some(if is_negative { value.negate() } else { value })
+    }
+}
+
+/// Scans `input` for integers, skipping everything in between.
+///
+/// Signed types pick up a directly preceding `-`, unsigned types ignore it, so
+/// `p=0,4 v=3,-3` scans to `0, 4, 3, -3` as `i32` and `0, 4, 3, 3` as `u32`.
+pub fn numbers<T: Integer, S: AsRef<[u8]> + ?Sized>(input: &S) -> Numbers<'_, T> {
+    Numbers {
+        bytes: input.as_ref(),
+        position: 0,
+        _integer: PhantomData,
+    }
+}
+
+/// Iterator over fixed-size groups of integers, see [`records`].
+#[derive(Debug, Clone)]
+pub struct Records<'a, T, const N: usize> {
+    numbers: Numbers<'a, T>,
+}
+
+impl<T: Integer, const N: usize> Iterator for Records<'_, T, N> {
+    type Item = [T; N];
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = [T::ZERO; N];
+        for slot in record.iter_mut() {
+            *slot = self.numbers.next()?;
+        }
+        Some(record)
+    }
+}
+
+/// Groups the integers of `input` into records of exactly `N` values, e.g. the two columns of
+/// day 1 or the six numbers describing a claw machine. A trailing partial record is dropped.
+pub fn records<T: Integer, const N: usize, S: AsRef<[u8]> + ?Sized>(
+    input: &S,
+) -> Records<'_, T, N> {
+    Records {
+        numbers: numbers(input),
+    }
+}
+
+/// Splits `input` into the blocks separated by blank lines, dropping empty ones.
+pub fn blocks(input: &str) -> impl Iterator<Item = &str> {
+    input
+        .split("\n\n")
+        .map(|block| block.trim_matches(['\n', '\r']))
+        .filter(|block| !block.is_empty())
+}
+
+/// Splits an input made of a grid followed by a blank line and a list of instructions.
+/// Line breaks inside the instructions are left for the caller to skip.
+pub fn grid_and_instructions(input: &str) -> Option<(&str, &str)> {
+    let (grid, instructions) = input.trim_end().split_once("\n\n")?;
+    Some((grid.trim_matches(['\n', '\r']), instructions))
+}
+
+/// `nom` parsers for the formats that keep showing up.
+pub mod combinators {
+    use std::str::FromStr;
+
+    use nom::{
+        bytes::complete::tag,
+        character::complete::{char, digit1, space1},
+        combinator::{map_res, opt, recognize},
+        multi::separated_list1,
+        sequence::{pair, separated_pair},
+        IResult,
+    };
+
+    /// A run of decimal digits.
+    pub fn unsigned<T: FromStr>(input: &str) -> IResult<&str, T> {
+        map_res(digit1, str::parse)(input)
+    }
+
+    /// A run of decimal digits with an optional leading `-`.
+    pub fn signed<T: FromStr>(input: &str) -> IResult<&str, T> {
+        map_res(recognize(pair(opt(char('-')), digit1)), str::parse)(input)
+    }
+
+    /// Two signed values separated by a comma, e.g. `6,-3`.
+    pub fn coordinate<T: FromStr>(input: &str) -> IResult<&str, (T, T)> {
+        separated_pair(signed, char(','), signed)(input)
+    }
+
+    /// Values separated by runs of spaces or tabs, e.g. `125 17`.
+    pub fn spaced<'a, T>(
+        item: impl FnMut(&'a str) -> IResult<&'a str, T>,
+    ) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<T>> {
+        separated_list1(space1, item)
+    }
+
+    /// Values separated by single commas, e.g. `0,1,5,4,3,0`.
+    pub fn comma_separated<'a, T>(
+        item: impl FnMut(&'a str) -> IResult<&'a str, T>,
+    ) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<T>> {
+        separated_list1(tag(","), item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::combinators::{comma_separated, coordinate, signed, spaced, unsigned};
+    use super::*;
+
+    #[test]
+    fn numbers_skip_separators() {
+        let values: Vec<u32> = numbers("3   4\n4   3\n2   5").collect();
+        assert_eq!(values, [3, 4, 4, 3, 2, 5]);
+    }
+
+    #[test]
+    fn numbers_scan_robot_lines() {
+        let values: Vec<i32> = numbers("p=0,4 v=3,-3").collect();
+        assert_eq!(values, [0, 4, 3, -3]);
+        let values: Vec<u32> = numbers("p=0,4 v=3,-3").collect();
+        assert_eq!(values, [0, 4, 3, 3]);
+    }
+
+    #[test]
+    fn numbers_respect_signedness() {
+        let input = "p=9,-5 v=-12,3";
+        assert_eq!(
+            numbers::<i64, _>(input).collect::<Vec<_>>(),
+            [9, -5, -12, 3]
+        );
+        assert_eq!(numbers::<u64, _>(input).collect::<Vec<_>>(), [9, 5, 12, 3]);
+    }
+
+    #[test]
+    fn numbers_handle_edges() {
+        assert_eq!(numbers::<u8, _>("").next(), None);
+        assert_eq!(numbers::<u8, _>("no digits").next(), None);
+        assert_eq!(numbers::<i16, _>("-7").collect::<Vec<_>>(), [-7]);
+        assert_eq!(
+            numbers::<u16, _>(b"12".as_slice()).collect::<Vec<_>>(),
+            [12]
+        );
+    }
+
+    #[test]
+    fn records_group_columns() {
+        let pairs: Vec<[u32; 2]> = records("3   4\n4   3\n").collect();
+        assert_eq!(pairs, [[3, 4], [4, 3]]);
+    }
+
+    #[test]
+    fn records_drop_partial_tail() {
+        let input = "Button A: X+94, Y+34\nButton B: X+22, Y+67\nPrize: X=8400, Y=5400\n\n1 2";
+        let machines: Vec<[i64; 6]> = records(input).collect();
+        assert_eq!(machines, [[94, 34, 22, 67, 8400, 5400]]);
+    }
+
+    #[test]
+    fn blocks_split_on_blank_lines() {
+        let input = "a\nb\n\nc\n\n\n\nd\n";
+        assert_eq!(blocks(input).collect::<Vec<_>>(), ["a\nb", "c", "d"]);
+    }
+
+    #[test]
+    fn grid_and_instructions_split() {
+        let input = "##\n#@\n\n<^\n>v\n";
+        assert_eq!(grid_and_instructions(input), Some(("##\n#@", "<^\n>v")));
+        assert_eq!(grid_and_instructions("##\n#@"), None);
+    }
+
+    #[test]
+    fn combinators_parse_common_formats() {
+        assert_eq!(unsigned::<u32>("42 rest"), Ok((" rest", 42)));
+        assert_eq!(signed::<i32>("-42,"), Ok((",", -42)));
+        assert_eq!(coordinate::<i32>("6,-3"), Ok(("", (6, -3))));
+        assert_eq!(spaced(unsigned::<u64>)("125 17"), Ok(("", vec![125, 17])));
+        assert_eq!(
+            comma_separated(unsigned::<u8>)("0,1,5\n"),
+            Ok(("\n", vec![0, 1, 5]))
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! A small seeded pseudo-random generator for self-checks and generated test inputs, where
+//! reproducibility matters more than statistical quality.
+
+use std::fmt::Debug;
+
+/// SplitMix64, see <https://prng.di.unimi.it/splitmix64.c>.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A value in `0..bound`. The bias is negligible for bounds far below `u64::MAX`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+}
+
+/// Runs `solve` and `reference` on `cases` inputs made by `generate` from a generator seeded with
+/// `seed`, panicking with the first input they disagree on.
+pub fn cross_check<I: Debug, O: PartialEq + Debug>(
+    seed: u64,
+    cases: usize,
+    mut generate: impl FnMut(&mut Rng) -> I,
+    solve: impl Fn(&I) -> O,
+    reference: impl Fn(&I) -> O,
+) {
+    let mut rng = Rng::new(seed);
+    for case in 0..cases {
+        let input = generate(&mut rng);
+        let (solved, expected) = (solve(&input), reference(&input));
+        assert!(
+            solved == expected,
+            "case {case} (seed {seed}): got {solved:?}, expected {expected:?} for {input:#?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_reference_output() {
+        let mut rng = Rng::new(0);
+        assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+    }
+
+    #[test]
+    fn below_stays_in_range() {
+        let mut rng = Rng::new(24);
+        let mut seen = [false; 7];
+        for _ in 0..1000 {
+            seen[rng.below(7) as usize] = true;
+        }
+        assert!(seen.iter().all(|&hit| hit));
+    }
+
+    #[test]
+    #[should_panic(expected = "case 0 (seed 1)")]
+    fn cross_check_reports_disagreement() {
+        cross_check(1, 10, |rng| 2 * rng.below(8) + 1, |&n| n | 1, |&n| n + 1);
+    }
+}
@@ -36,6 +36,18 @@ pub fn read_file_part(folder: &str, day: Day, part: u8) -> String {
     f.expect("could not open input file")
 }
 
+/// Helper function that checks whether a solution was started with the option `name`, e.g. `--trace`.
+#[must_use]
+pub fn has_flag(name: &str) -> bool {
+    env::args().any(|arg| arg == name)
+}
+
+/// Helper function that returns the value following the option `name`, e.g. the path in `--dot circuit.dot`.
+#[must_use]
+pub fn flag_value(name: &str) -> Option<String> {
+    env::args().skip_while(|arg| arg != name).nth(1)
+}
+
 /// Creates the constant `DAY` and sets up the input and runner for each part.
 ///
 /// The optional, second parameter (1 or 2) allows you to only run a single part of the solution.
@@ -6,6 +6,7 @@ use std::process::Output;
 use std::time::{Duration, Instant};
 use std::{cmp, env, process};
 
+use crate::ocr;
 use crate::template::ANSI_BOLD;
 use crate::template::{aoc_cli, Day, ANSI_ITALIC, ANSI_RESET};
 
@@ -107,6 +108,9 @@ fn print_result<T: Display>(result: &Option<T>, part: &str, duration_str: &str)
                     print!("\r");
                     println!("{str}");
                     println!("{result}");
+                    if let Some(text) = ocr::recognize_str(&result.to_string()) {
+                        println!("Reads: {ANSI_BOLD}{text}{ANSI_RESET}");
+                    }
                 }
             } else {
                 let str = format!("{part}: {ANSI_BOLD}{result}{ANSI_RESET}{duration_str}");
@@ -164,6 +168,10 @@ fn submit_result<T: Display>(
         process::exit(1);
     }
 
+    // letter art is submitted as the text it spells out
+    let result = result.to_string();
+    let result = ocr::recognize_str(&result).unwrap_or(result);
+
     println!("Submitting result via aoc-cli...");
-    Some(aoc_cli::submit(day, part, &result.to_string()))
+    Some(aoc_cli::submit(day, part, &result))
 }